@@ -0,0 +1,17 @@
+//! The sound effects and mixer of the real `audio` module, without the I2S output they feed
+
+use core::mem::size_of;
+
+#[path = "../../src/audio/mixer.rs"]
+pub mod mixer;
+// `pattern_demo` leaves two variables unused, the firmware build warns about them too
+#[allow(unused_variables)]
+#[path = "../../src/audio/sfx.rs"]
+pub mod sfx;
+
+pub type Sample = i16;
+pub const SAMPLE_RATE: u32 = 22_050;
+const CH: usize = 2;
+const BYTES_PER_FRAME: usize = CH * size_of::<Sample>();
+const DMA_CHUNK: usize = BYTES_PER_FRAME * 8 * 2;
+const DMA_TOTAL: usize = DMA_CHUNK * 2;
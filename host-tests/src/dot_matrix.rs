@@ -0,0 +1,56 @@
+/// Drawing into memory can't fail, there is no SPI transfer
+#[derive(Debug)]
+pub enum DotMatrixError {}
+
+/// The frame buffer half of the real `DotMatrix`, drawing into memory instead of over SPI
+#[derive(Default)]
+pub struct DotMatrix<'a> {
+    buffer: [u8; 8],
+    _display: core::marker::PhantomData<&'a ()>,
+}
+
+impl DotMatrix<'_> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn buffer(&self) -> &[u8; 8] {
+        &self.buffer
+    }
+
+    pub fn fill(&mut self) {
+        self.buffer = [0xff; 8];
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer = [0; 8];
+    }
+
+    pub fn put(&mut self, x: u8, y: u8) {
+        assert!(x < 8 && y < 8, "({x}, {y}) is off the display");
+        self.buffer[y as usize] |= 0b10000000 >> x;
+    }
+
+    pub fn set_row(&mut self, row: u8, row_data: u8) {
+        self.buffer[row as usize] = row_data;
+    }
+
+    pub fn draw<const ROWS: usize>(&mut self, bitmap: &[u8; ROWS]) {
+        self.buffer[0..ROWS].copy_from_slice(&bitmap[0..ROWS]);
+    }
+
+    pub fn shift(&mut self, x: u8, y: u8) {
+        for row in &mut self.buffer {
+            *row >>= x;
+        }
+        self.buffer.rotate_right(y as usize);
+    }
+
+    pub async fn set_intensity(&mut self, _intensity: u8) -> Result<(), DotMatrixError> {
+        Ok(())
+    }
+
+    pub async fn flush_buffer_to_spi(&mut self) -> Result<(), DotMatrixError> {
+        Ok(())
+    }
+}
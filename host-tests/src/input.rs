@@ -0,0 +1,6 @@
+//! The button events of the real `input` module, without the GPIO, encoder and console sources
+
+#[path = "../../src/input/event.rs"]
+mod event;
+
+pub use event::{ButtonEvent, InputEvent, Player, LONG_PRESS};
@@ -0,0 +1,39 @@
+//! The hardware independent parts of the game built for the host, so their `#[cfg(test)]` modules
+//! can run with `cargo test` from this directory. Modules are included straight from `../src`, only
+//! the display, the audio output and the input sources are left out here.
+
+pub mod audio;
+#[path = "../../src/ball.rs"]
+pub mod ball;
+#[path = "../../src/difficulty.rs"]
+pub mod difficulty;
+pub mod dot_matrix;
+#[path = "../../src/fixed.rs"]
+pub mod fixed;
+#[path = "../../src/font.rs"]
+pub mod font;
+#[path = "../../src/game_config.rs"]
+pub mod game_config;
+#[path = "../../src/game_mode/mod.rs"]
+pub mod game_mode;
+#[path = "../../src/game_state.rs"]
+pub mod game_state;
+#[path = "../../src/highscore.rs"]
+pub mod highscore;
+pub mod input;
+#[path = "../../src/level.rs"]
+pub mod level;
+#[path = "../../src/pad.rs"]
+pub mod pad;
+#[path = "../../src/power_up.rs"]
+pub mod power_up;
+#[path = "../../src/rng.rs"]
+pub mod rng;
+#[path = "../../src/scoring.rs"]
+pub mod scoring;
+#[path = "../../src/stats.rs"]
+pub mod stats;
+#[path = "../../src/storage/mod.rs"]
+pub mod storage;
+#[path = "../../src/text_ticker.rs"]
+pub mod text_ticker;
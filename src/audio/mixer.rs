@@ -15,6 +15,8 @@ fn as_i16(b: &[u8]) -> &[i16] {
 }
 
 const MAX_CHANS: usize = 8;
+/// Full gain, see `Mixer::set_volume`
+pub const MAX_VOLUME: u8 = 10;
 pub const PIPE_BYTES: usize = DMA_TOTAL * 30;
 
 // one global bank
@@ -51,6 +53,14 @@ impl<const N: usize> Mixer<N> {
         self.gains_q15[idx] = q15;
     }
 
+    /// The same gain for every channel, from silent at 0 to full at `MAX_VOLUME`
+    pub fn set_volume(&mut self, volume: u8) {
+        let q15 = (0x7FFF * volume.min(MAX_VOLUME) as u32 / MAX_VOLUME as u32) as u16;
+        for idx in 0..N {
+            self.set_gain_q15(idx, q15);
+        }
+    }
+
     /// Mix N channels into `dst` (bytes, multiple of frame size).
     /// Strategy: zero `dst` once, then for each channel, stream-read in small chunks
     /// and accumulate (scaled) into `dst`. Underrun == add silence.
@@ -1,6 +1,7 @@
 // sfx.rs
 #![allow(dead_code)]
 use core::f32::consts::TAU;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
 use embedded_io_async::Write;
 use libm::{asinf, floorf, sinf};
 use log::warn;
@@ -229,6 +230,63 @@ pub async fn noise_burst(tx: AudioProducerChannel, ms: u32, gain_q15: u16, pan_q
     .await
 }
 
+/// Sounds the game logic can ask for without waiting for them to be played
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundEffect {
+    /// The button went down
+    Press,
+    SlowMotion,
+    Shield,
+    ShieldBroken,
+    Health,
+    Multiplier,
+    Brick,
+    LevelCleared,
+}
+
+static SOUND_EFFECTS: Channel<CriticalSectionRawMutex, SoundEffect, 4> = Channel::new();
+
+/// Queue a sound effect, dropping it if too many are already waiting
+pub fn trigger(effect: SoundEffect) {
+    if SOUND_EFFECTS.try_send(effect).is_err() {
+        warn!("Too many sound effects queued, dropping {effect:?}");
+    }
+}
+
+/// Plays triggered sound effects, one at a time. The only thing writing to `tx`, so effects never
+/// get mixed into each other's samples.
+pub async fn run_sound_effects(tx: AudioProducerChannel) -> ! {
+    loop {
+        match SOUND_EFFECTS.receive().await {
+            SoundEffect::Press => blip(tx, 440.0, 50, 0x05FF, 0).await,
+            SoundEffect::SlowMotion => {
+                beep(tx, 330.0, 120, 0x3FFF, 0).await;
+                beep(tx, 220.0, 200, 0x3FFF, 0).await;
+            }
+            SoundEffect::Shield => {
+                blip(tx, 660.0, 60, 0x2FFF, 0).await;
+                blip(tx, 990.0, 90, 0x2FFF, 0).await;
+            }
+            SoundEffect::ShieldBroken => noise_burst(tx, 150, 0x4FFF, 0).await,
+            SoundEffect::Health => {
+                beep(tx, 523.0, 80, 0x3FFF, 0).await;
+                beep(tx, 784.0, 160, 0x3FFF, 0).await;
+            }
+            SoundEffect::Multiplier => {
+                for hz in [440.0, 554.0, 659.0, 880.0] {
+                    blip(tx, hz, 50, 0x2FFF, 0).await;
+                }
+            }
+            SoundEffect::Brick => blip(tx, 1320.0, 30, 0x2FFF, 0).await,
+            SoundEffect::LevelCleared => {
+                for hz in [523.0, 659.0, 784.0, 1047.0] {
+                    beep(tx, hz, 90, 0x3FFF, 0).await;
+                }
+            }
+        }
+    }
+}
+
 /// arpeggiate notes (Hz list) with step_ms per note
 pub async fn arpeggio<const BYTES: usize>(
     tx: AudioProducerChannel,
@@ -1,105 +1,524 @@
 use crate::{
+    difficulty::DifficultySettings,
     dot_matrix::DotMatrix,
-    pad::{Pad, PadPosition, PadState},
+    fixed::Fixed,
+    game_config::GameConfig,
+    level::Level,
+    pad::{Pad, PadState, Side},
 };
 
+const ARENA_SIZE: Fixed = Fixed::from_int(8);
+const LAST_PIXEL: Fixed = Fixed::from_int(7);
+/// The furthest a ball moves before checking for collisions
+const MAX_STEP: Fixed = Fixed::from_f32(0.25);
+
+/// What a ball bounced off during an update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bounce {
+    Wall,
+    Brick,
+    Pad,
+}
+
 #[derive(Clone)]
 pub struct Ball {
-    pub x: f32,
-    pub y: f32,
-    x_speed: f32,
-    y_speed: f32,
+    pub x: Fixed,
+    pub y: Fixed,
+    // Where the ball was before the last update, to draw it in between
+    previous_x: Fixed,
+    previous_y: Fixed,
+    // Pixels per second, per ms would be too coarse for the acceleration to register
+    x_speed: Fixed,
+    y_speed: Fixed,
+    // Relative speed increase per second
+    acceleration: Fixed,
 }
 impl Ball {
-    pub const fn new(initial_x: u8, initial_y: u8) -> Self {
+    pub fn new(initial_x: u8, initial_y: u8, settings: &DifficultySettings) -> Self {
+        let (x, y) = (
+            Fixed::from_int(initial_x as i32),
+            Fixed::from_int(initial_y as i32),
+        );
         Self {
-            x: initial_x as f32,
-            y: initial_y as f32,
-            x_speed: 0.0054,
-            y_speed: -0.004,
+            x,
+            y,
+            previous_x: x,
+            previous_y: y,
+            x_speed: Fixed::from_f32(settings.x_speed * 1000.0),
+            y_speed: Fixed::from_f32(settings.y_speed * 1000.0),
+            acceleration: Fixed::from_f32(settings.acceleration * 1000.0),
         }
     }
 
-    pub fn update(&mut self, pad: &mut Pad, delta_time_ms: u64, score: &mut u32) {
-        let mut pad_hit = false;
-        let mut hit = false;
-        if let Pad::Alive {
+    /// A copy heading off in the mirrored direction, for multi-ball
+    pub fn split(&self) -> Self {
+        Self {
+            x_speed: -self.x_speed,
+            ..self.clone()
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        pad: &Pad,
+        level: &mut Level,
+        delta_time_ms: u64,
+        config: &GameConfig,
+    ) -> Option<Bounce> {
+        self.previous_x = self.x;
+        self.previous_y = self.y;
+
+        let Pad::Alive {
             position,
             state: PadState::Normal,
             ..
-        } = &pad
-        {
-            self.x_speed *= 1.0 + (0.00001 * delta_time_ms as f32);
-            self.y_speed *= 1.0 + (0.00001 * delta_time_ms as f32);
-
-            // Check X collision
-            if self.x_speed < 0.0 {
-                let min_x = if matches!(position, PadPosition::Left(_)) {
-                    1.5
-                } else {
-                    0.5
-                };
-                if self.x < min_x {
-                    hit = true;
-                    pad_hit = matches!(position, PadPosition::Left(_));
-                    self.x = min_x;
-                    self.x_speed *= -1.0;
-                }
+        } = pad
+        else {
+            return None;
+        };
+
+        let delta_time_ms = delta_time_ms as i64;
+        self.x_speed += (self.x_speed * self.acceleration).per_ms(delta_time_ms);
+        self.y_speed += (self.y_speed * self.acceleration).per_ms(delta_time_ms);
+        let speed = self.x_speed.abs() + self.y_speed.abs();
+        if speed > config.max_ball_speed {
+            self.x_speed = self.x_speed * (config.max_ball_speed / speed);
+            self.y_speed = self.y_speed * (config.max_ball_speed / speed);
+        }
+
+        let near_wall = config.bounce_margin;
+        let near_pad = config.bounce_margin + config.pad_thickness;
+        let bound = |side| {
+            if position.guards(side) {
+                near_pad
             } else {
-                let max_x = if matches!(position, PadPosition::Right(_)) {
-                    6.5
-                } else {
-                    7.5
-                };
-
-                if self.x >= max_x {
-                    hit = true;
-                    pad_hit = matches!(position, PadPosition::Right(_));
-                    self.x = max_x;
-                    self.x_speed *= -1.0;
-                }
+                near_wall
             }
+        };
+        let (min_x, max_x) = (bound(Side::Left), ARENA_SIZE - bound(Side::Right));
+        let (min_y, max_y) = (bound(Side::Top), ARENA_SIZE - bound(Side::Bottom));
 
-            // Check Y collision
-            if self.y_speed < 0.0 {
-                let min_y = if matches!(position, PadPosition::Top(_)) {
-                    1.5
-                } else {
-                    0.5
-                };
-                if self.y < min_y {
-                    hit = true;
-                    pad_hit = matches!(position, PadPosition::Top(_));
-                    self.y = min_y;
-                    self.y_speed *= -1.0;
+        // Move in steps short enough that a fast ball can't skip past a wall or the pad
+        let distance = Fixed::max(self.x_speed.abs(), self.y_speed.abs()).per_ms(delta_time_ms);
+        let steps = distance.steps_of(MAX_STEP).max(1);
+
+        let mut pad_hit = false;
+        let mut brick_hit = false;
+        let mut hit = false;
+        for _ in 0..steps {
+            let (from_x, from_y) = (self.x, self.y);
+            self.x += self.x_speed.scale(delta_time_ms, 1000 * steps as i64);
+            self.y += self.y_speed.scale(delta_time_ms, 1000 * steps as i64);
+
+            let x_sides = (Side::Left, Side::Right);
+            if let Some(side) = Self::collide(&mut self.x, &mut self.x_speed, min_x, max_x, x_sides)
+            {
+                hit = true;
+                if let Some(offset) = position.hit(side, self.y) {
+                    pad_hit = true;
+                    Self::deflect(offset, &mut self.y_speed, &mut self.x_speed, config);
                 }
-            } else {
-                let max_y = if matches!(position, PadPosition::Bottom(_)) {
-                    6.5
-                } else {
-                    7.5
-                };
-                if self.y >= max_y {
-                    hit = true;
-                    pad_hit = matches!(position, PadPosition::Bottom(_));
-                    self.y = max_y;
-                    self.y_speed *= -1.0;
+            }
+            let y_sides = (Side::Top, Side::Bottom);
+            if let Some(side) = Self::collide(&mut self.y, &mut self.y_speed, min_y, max_y, y_sides)
+            {
+                hit = true;
+                if let Some(offset) = position.hit(side, self.x) {
+                    pad_hit = true;
+                    Self::deflect(offset, &mut self.x_speed, &mut self.y_speed, config);
                 }
             }
-            if hit {
-                if pad_hit {
-                    pad.take_damage();
-                } else {
-                    *score += 1;
+            if let Some(broken) = self.collide_blocks(from_x, from_y, level) {
+                hit = true;
+                brick_hit |= broken;
+            }
+        }
+
+        match (hit, brick_hit, pad_hit) {
+            (_, _, true) => Some(Bounce::Pad),
+            (_, true, false) => Some(Bounce::Brick),
+            (true, false, false) => Some(Bounce::Wall),
+            (false, _, false) => None,
+        }
+    }
+
+    /// Steps back and bounces if the ball moved into a block, returns whether it broke a brick.
+    /// Checking which of the two neighbouring cells is solid tells if it came in from the side, from
+    /// above/below, or straight into a corner.
+    fn collide_blocks(&mut self, from_x: Fixed, from_y: Fixed, level: &mut Level) -> Option<bool> {
+        let (x, y) = (self.x.to_int() as u8, self.y.to_int() as u8);
+        if !level.is_solid(x, y) {
+            return None;
+        }
+        let (from_cell_x, from_cell_y) = (from_x.to_int() as u8, from_y.to_int() as u8);
+        let side = level.is_solid(x, from_cell_y);
+        let end = level.is_solid(from_cell_x, y);
+
+        let mut broken = false;
+        if side {
+            self.x = from_x;
+            self.x_speed = -self.x_speed;
+            broken |= level.hit(x, from_cell_y);
+        }
+        if end {
+            self.y = from_y;
+            self.y_speed = -self.y_speed;
+            broken |= level.hit(from_cell_x, y);
+        }
+        if !side && !end {
+            self.x = from_x;
+            self.y = from_y;
+            self.x_speed = -self.x_speed;
+            self.y_speed = -self.y_speed;
+            broken |= level.hit(x, y);
+        }
+        Some(broken)
+    }
+
+    /// Bends the bounce off the pad depending on where along the pad it hit (`offset`, -1..1 from
+    /// the middle): the further out, the more the ball heads off towards that end. The total speed (`|along| + |across|`)
+    /// stays the same and neither direction gets less than `min_angle` of it, so the ball never
+    /// ends up sliding along the pad or bouncing straight back and forth.
+    fn deflect(offset: Fixed, along: &mut Fixed, across: &mut Fixed, config: &GameConfig) {
+        let speed = along.abs() + across.abs();
+        let minimum = speed * config.min_angle.clamp(Fixed::ZERO, Fixed::HALF);
+
+        let deflected = *along + speed * config.pad_deflection * offset;
+        let direction = if deflected == Fixed::ZERO {
+            along.signum()
+        } else {
+            deflected.signum()
+        };
+        // With `min_angle` at a half, rounding can leave `minimum` a hair over half the speed
+        let maximum = (speed - minimum).max(minimum);
+        let deflected = deflected.abs().clamp(minimum, maximum);
+
+        *along = deflected * direction;
+        *across = (speed - deflected) * across.signum();
+    }
+
+    /// The two sides the ball is heading towards, it will hit one of them next and then the other
+    pub fn heading(&self) -> [Side; 2] {
+        [
+            if self.x_speed < Fixed::ZERO {
+                Side::Left
+            } else {
+                Side::Right
+            },
+            if self.y_speed < Fixed::ZERO {
+                Side::Top
+            } else {
+                Side::Bottom
+            },
+        ]
+    }
+
+    /// Seconds until the ball reaches one of the sides it is heading towards, ignoring blocks
+    pub fn time_to_impact(&self) -> Fixed {
+        Fixed::min(
+            Self::time_to_side(self.x, self.x_speed),
+            Self::time_to_side(self.y, self.y_speed),
+        )
+    }
+
+    /// Seconds until the ball reaches the line it would bounce off a pad on `side`, going by how
+    /// fast it moves towards that side only. `Fixed::MAX` if it is moving away from it.
+    pub fn time_to_pad(&self, side: Side, config: &GameConfig) -> Fixed {
+        let near_pad = config.bounce_margin + config.pad_thickness;
+        let (distance, speed) = match side {
+            Side::Left => (self.x - near_pad, -self.x_speed),
+            Side::Right => (ARENA_SIZE - near_pad - self.x, self.x_speed),
+            Side::Top => (self.y - near_pad, -self.y_speed),
+            Side::Bottom => (ARENA_SIZE - near_pad - self.y, self.y_speed),
+        };
+        if speed <= Fixed::ZERO {
+            Fixed::MAX
+        } else {
+            distance.max(Fixed::ZERO) / speed
+        }
+    }
+
+    /// The side the ball reaches next and where along it (x for the top and bottom, y for the
+    /// sides), ignoring blocks
+    pub fn impact(&self) -> (Side, Fixed) {
+        let [x_side, y_side] = self.heading();
+        let x_time = Self::time_to_side(self.x, self.x_speed);
+        let y_time = Self::time_to_side(self.y, self.y_speed);
+        if x_time < y_time {
+            (x_side, self.y + self.y_speed * x_time)
+        } else {
+            (y_side, self.x + self.x_speed * y_time)
+        }
+    }
+
+    /// Seconds until a ball at `position` going at `speed` along one axis reaches the end it is
+    /// heading towards
+    fn time_to_side(position: Fixed, speed: Fixed) -> Fixed {
+        let distance = if speed < Fixed::ZERO {
+            position
+        } else {
+            ARENA_SIZE - position
+        };
+        if speed == Fixed::ZERO {
+            Fixed::MAX
+        } else {
+            distance / speed.abs()
+        }
+    }
+
+    /// Turns the ball around if it has moved past `min` or `max` along one axis, returning which of
+    /// the two sides it bounced off
+    fn collide(
+        position: &mut Fixed,
+        speed: &mut Fixed,
+        min: Fixed,
+        max: Fixed,
+        (min_side, max_side): (Side, Side),
+    ) -> Option<Side> {
+        if *speed < Fixed::ZERO && *position < min {
+            *position = min;
+            *speed = -*speed;
+            Some(min_side)
+        } else if *speed >= Fixed::ZERO && *position >= max {
+            *position = max;
+            *speed = -*speed;
+            Some(max_side)
+        } else {
+            None
+        }
+    }
+
+    /// Draws the ball `alpha` (0..1) of the way from its previous to its current position
+    pub fn draw(&self, alpha: Fixed, dot_matrix: &mut DotMatrix) {
+        let x = self.previous_x + (self.x - self.previous_x) * alpha;
+        let y = self.previous_y + (self.y - self.previous_y) * alpha;
+        dot_matrix.put(x.to_int() as u8, y.to_int() as u8);
+    }
+
+    /// A pixel trailing behind the ball
+    pub fn draw_trail(&self, dot_matrix: &mut DotMatrix) {
+        let behind = |position: Fixed, speed: Fixed| {
+            (position - speed.signum())
+                .clamp(Fixed::ZERO, LAST_PIXEL)
+                .to_int() as u8
+        };
+        dot_matrix.put(behind(self.x, self.x_speed), behind(self.y, self.y_speed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{difficulty::Difficulty, pad::PadPosition, rng::Rng};
+
+    /// Mostly frame-sized steps, with the odd stall and the odd zero
+    fn delta_time_ms(rng: &mut Rng) -> u64 {
+        match rng.range(0..10) {
+            0 => rng.range(0..400) as u64,
+            1 => 0,
+            _ => rng.range(1..40) as u64,
+        }
+    }
+
+    /// Plays `runs` games with random frame times and random presses, checking after every update
+    /// that the ball is still in the arena and can be drawn
+    fn stays_in_arena(config: &GameConfig, seed: u32, runs: usize) {
+        let mut rng = Rng::new(seed);
+        for run in 0..runs {
+            let settings = config.difficulty(Difficulty::ALL[run % Difficulty::COUNT]);
+            let mut ball = Ball::new(3, 3, settings);
+            let mut level = Level::new(run);
+            let mut pad = Pad::new(PadPosition::start(config), u8::MAX);
+            for _ in 0..2000 {
+                let delta_time_ms = delta_time_ms(&mut rng);
+                if rng.range(0..30) == 0 {
+                    if let Pad::Alive { position, .. } = &mut pad {
+                        position.next();
+                    }
+                }
+                pad.update(delta_time_ms, settings.pad_slide_speed);
+                if ball.update(&pad, &mut level, delta_time_ms, config) == Some(Bounce::Pad) {
+                    pad.take_damage(config);
                 }
+                if let Pad::Dead = pad {
+                    pad = Pad::new(PadPosition::start(config), u8::MAX);
+                }
+
+                let arena = Fixed::ZERO..ARENA_SIZE;
+                assert!(
+                    arena.contains(&ball.x) && arena.contains(&ball.y),
+                    "run {run}: ball left the arena at ({}, {})",
+                    ball.x.to_f32(),
+                    ball.y.to_f32()
+                );
+                let mut dot_matrix = DotMatrix::new();
+                ball.draw(Fixed::HALF, &mut dot_matrix);
+                ball.draw_trail(&mut dot_matrix);
             }
+        }
+    }
 
-            self.x += self.x_speed * delta_time_ms as f32;
-            self.y += self.y_speed * delta_time_ms as f32;
+    /// The ball as it moved with `f32` before the fixed-point switch: bouncing like a mirror off
+    /// the walls and a pad on the bottom, with the speeds in pixels per ms
+    struct FloatBall {
+        x: f32,
+        y: f32,
+        x_speed: f32,
+        y_speed: f32,
+        acceleration: f32,
+    }
+
+    impl FloatBall {
+        fn update(&mut self, delta_time_ms: u64, config: &GameConfig) {
+            let delta_time_ms = delta_time_ms as f32;
+            self.x_speed *= 1.0 + self.acceleration * delta_time_ms;
+            self.y_speed *= 1.0 + self.acceleration * delta_time_ms;
+
+            let margin = config.bounce_margin.to_f32();
+            let (min_x, max_x) = (margin, 8.0 - margin);
+            let (min_y, max_y) = (margin, 8.0 - margin - config.pad_thickness.to_f32());
+
+            let distance = f32::max(self.x_speed.abs(), self.y_speed.abs()) * delta_time_ms;
+            let steps = (distance / MAX_STEP.to_f32()).ceil().max(1.0);
+            for _ in 0..steps as u32 {
+                self.x += self.x_speed * delta_time_ms / steps;
+                self.y += self.y_speed * delta_time_ms / steps;
+                Self::collide(&mut self.x, &mut self.x_speed, min_x, max_x);
+                Self::collide(&mut self.y, &mut self.y_speed, min_y, max_y);
+            }
         }
+
+        fn collide(position: &mut f32, speed: &mut f32, min: f32, max: f32) {
+            if (*speed < 0.0 && *position < min) || (*speed >= 0.0 && *position >= max) {
+                *position = position.clamp(min, max);
+                *speed = -*speed;
+            }
+        }
+    }
+
+    #[test]
+    fn follows_float_trajectory() {
+        // Mirror bounces, no speed cap and nothing in the way, as the float version had
+        let config = GameConfig {
+            pad_deflection: Fixed::ZERO,
+            min_angle: Fixed::ZERO,
+            max_ball_speed: Fixed::from_int(64),
+            ..GameConfig::default()
+        };
+        let pad = Pad::new(PadPosition::Bottom(Fixed::ONE), 1);
+
+        let mut rng = Rng::new(36);
+        for difficulty in Difficulty::ALL {
+            let settings = difficulty.settings();
+            let mut ball = Ball::new(3, 3, &settings);
+            let mut float_ball = FloatBall {
+                x: 3.0,
+                y: 3.0,
+                x_speed: settings.x_speed,
+                y_speed: settings.y_speed,
+                acceleration: settings.acceleration,
+            };
+            let mut level = Level::new(0);
+            for (x, y) in (0..8).flat_map(|x| (0..8).map(move |y| (x, y))) {
+                level.clear(x, y);
+            }
+
+            for step in 0..1000 {
+                let delta_time_ms = rng.range(1..40) as u64;
+                ball.update(&pad, &mut level, delta_time_ms, &config);
+                float_ball.update(delta_time_ms, &config);
+                let (x_error, y_error) = (
+                    (ball.x.to_f32() - float_ball.x).abs(),
+                    (ball.y.to_f32() - float_ball.y).abs(),
+                );
+                assert!(
+                    x_error < 0.01 && y_error < 0.01,
+                    "{difficulty:?} step {step}: ({}, {}) vs ({}, {})",
+                    ball.x.to_f32(),
+                    ball.y.to_f32(),
+                    float_ball.x,
+                    float_ball.y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn deflects_at_half_min_angle() {
+        let config = GameConfig {
+            min_angle: Fixed::HALF,
+            ..GameConfig::default()
+        };
+        for offset in [-Fixed::ONE, Fixed::ZERO, Fixed::HALF] {
+            // An odd speed in Q16.16, whose half gets rounded up
+            let (mut along, mut across) = (Fixed::from_ratio(3, 1 << 16), -Fixed::ONE);
+            let speed = along.abs() + across.abs();
+            Ball::deflect(offset, &mut along, &mut across, &config);
+            assert_eq!(along.abs() + across.abs(), speed);
+            assert!(across < Fixed::ZERO);
+        }
+    }
+
+    #[test]
+    fn predicts_impact() {
+        // Easy starts off at 4.2 pixels per second right and 3.1 up, so it reaches the top first
+        let ball = Ball::new(3, 3, &Difficulty::Easy.settings());
+        let (side, along) = ball.impact();
+        assert_eq!(side, Side::Top);
+        assert!((along.to_f32() - (3.0 + 4.2 * 3.0 / 3.1)).abs() < 0.01);
     }
 
-    pub fn draw(&self, dot_matrix: &mut DotMatrix) {
-        dot_matrix.put(self.x as u8, self.y as u8);
+    /// A ball at (`x`, `y`) going `x_speed` and `y_speed` pixels per second
+    fn moving_ball(x: f32, y: f32, x_speed: f32, y_speed: f32) -> Ball {
+        Ball {
+            x: Fixed::from_f32(x),
+            y: Fixed::from_f32(y),
+            x_speed: Fixed::from_f32(x_speed),
+            y_speed: Fixed::from_f32(y_speed),
+            ..Ball::new(0, 0, &Difficulty::Easy.settings())
+        }
+    }
+
+    #[test]
+    fn times_pad_about_to_be_hit() {
+        // The bottom pad's bounce line is at 8 - 0.5 - 1, 0.1 pixels below the ball
+        let config = GameConfig::default();
+        let ball = moving_ball(4.0, 6.4, 0.5, 2.0);
+        assert!((ball.time_to_pad(Side::Bottom, &config).to_f32() - 0.05).abs() < 0.001);
+        assert!(ball.time_to_pad(Side::Bottom, &config) < ball.time_to_impact());
+    }
+
+    #[test]
+    fn times_pad_while_heading_at_another_wall() {
+        // About to hit the right wall, the bottom pad is still 3.5 pixels away at 1 per second
+        let config = GameConfig::default();
+        let ball = moving_ball(7.4, 3.0, 4.0, 1.0);
+        assert!(ball.time_to_impact().to_f32() < 0.2);
+        assert!((ball.time_to_pad(Side::Bottom, &config).to_f32() - 3.5).abs() < 0.001);
+        assert_eq!(ball.time_to_pad(Side::Top, &config), Fixed::MAX);
+    }
+
+    #[test]
+    fn stays_in_arena_with_side_pad() {
+        stays_in_arena(&GameConfig::default(), 0x1234_5678, 500);
+    }
+
+    #[test]
+    fn stays_in_arena_with_orbit_pad() {
+        let config = GameConfig {
+            orbit_pad: true,
+            ..GameConfig::default()
+        };
+        stays_in_arena(&config, 0x8765_4321, 500);
+    }
+
+    #[test]
+    fn stays_in_arena_at_top_speed() {
+        let mut config = GameConfig::default();
+        config.set("max_ball_speed", 64.0).unwrap();
+        for settings in &mut config.difficulties {
+            settings.acceleration = 0.001;
+        }
+        stays_in_arena(&config, 99, 100);
     }
 }
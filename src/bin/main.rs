@@ -7,24 +7,34 @@
 )]
 
 use embassy_executor::{task, SendSpawner, Spawner};
-use esp32c3_test1::audio::mixer::{AudioProducerChannel, Mixer};
+use esp32c3_test1::audio::mixer::{AudioProducerChannel, Mixer, MAX_VOLUME};
 use esp32c3_test1::audio::run_audio_loop;
-use esp32c3_test1::audio::sfx::{self, pattern_demo};
-use esp32c3_test1::game_state::GameState;
+use esp32c3_test1::audio::sfx::{self, pattern_demo, SoundEffect};
+use esp32c3_test1::fixed::Fixed;
+use esp32c3_test1::game_config::{ConfigError, ConfigStore, GameConfig};
+use esp32c3_test1::game_mode::Selection;
+use esp32c3_test1::game_state::{GameState, DEFAULT_BRIGHTNESS, MAX_BRIGHTNESS};
 use esp32c3_test1::highscore::HighScore;
+use esp32c3_test1::input::button::GestureRecogniser;
+use esp32c3_test1::input::console::{Console, ConsoleCommand, ConsoleCommandChannel};
+use esp32c3_test1::input::encoder::RotaryEncoder;
+use esp32c3_test1::input::{forward, post, ButtonEvent, InputEventChannel, InputSource, Player};
+use esp32c3_test1::stats::Stats;
+use esp32c3_test1::storage::settings::{Key, Settings};
 use esp_hal::interrupt::software::SoftwareInterruptControl;
 use esp_hal::interrupt::Priority;
 use esp_hal_embassy::InterruptExecutor;
 
-use core::sync::atomic::AtomicBool;
-use embassy_time::{Duration, Instant, Timer};
+use embassy_time::{Duration, Instant, Ticker};
 use esp32c3_test1::dot_matrix::DotMatrix;
 use esp32c3_test1::text_ticker::TextTicker;
 use esp_hal::clock::CpuClock;
 use esp_hal::gpio::{Input, InputConfig, Pull};
 use esp_hal::timer::systimer::SystemTimer;
-use heapless::{format, String};
-use log::{error, info};
+use esp_hal::usb_serial_jtag::{UsbSerialJtag, UsbSerialJtagRx};
+use esp_hal::Async;
+use heapless::String;
+use log::{error, info, warn};
 #[panic_handler]
 fn panic(i: &core::panic::PanicInfo) -> ! {
     info!("PANIC: {i:?}");
@@ -35,45 +45,150 @@ fn panic(i: &core::panic::PanicInfo) -> ! {
 // For more information see: <https://docs.espressif.com/projects/esp-idf/en/stable/esp32/api-reference/system/app_image_format.html#application-description>
 esp_bootloader_esp_idf::esp_app_desc!();
 
-static BTN_DOWN: AtomicBool = AtomicBool::new(false);
+static INPUT_EVENTS: InputEventChannel = InputEventChannel::new();
+static CONSOLE_COMMANDS: ConsoleCommandChannel = ConsoleCommandChannel::new();
 const MIXER_CHANNELS: usize = 2;
+const SIM_STEP: Duration = Duration::from_millis(4);
+const FRAME_TIME: Duration = Duration::from_millis(16);
+/// Never simulate more than this in one frame, so a slow frame can't snowball into slower ones
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
 
 #[embassy_executor::task]
 async fn game_loop(
     mut dot_matrix: DotMatrix<'static>,
+    mut settings: Settings,
     mut highscore: HighScore,
+    mut config_store: ConfigStore,
     intro_message_override: Option<&'static str>,
 ) {
     info!("Starting game loop!");
-    let mut last_tick = Instant::now();
-
-    let mut game_state = GameState::Intro(TextTicker::new(
-        intro_message_override
-            .map(|s| String::try_from(s).expect("a string"))
-            .unwrap_or_else(|| format!(" Highscore:{}", highscore.get()).expect("a string")),
-        0.008,
-    ));
+    let mut config = config_store.load();
+    let selection = settings.get_or(Key::Selection, Selection::default());
+    let mut brightness = settings.get_or(Key::Brightness, DEFAULT_BRIGHTNESS);
+
+    let mut game_state = match intro_message_override {
+        Some(message) => GameState::Intro(
+            TextTicker::new(
+                String::try_from(message).expect("a string"),
+                config.intro_ticker_speed,
+            ),
+            selection,
+            config.attract_idle_ms,
+        ),
+        None => GameState::intro(selection, &highscore, &config),
+    };
+
+    // The simulation runs in fixed steps up to `sim_time`, the time between that and now is the
+    // accumulator left over for the next frame
+    let mut sim_time = Instant::now();
+    let mut pending_event = None;
+    let mut frames = Ticker::every(FRAME_TIME);
     loop {
+        while let Ok(command) = CONSOLE_COMMANDS.try_receive() {
+            handle_console_command(command, &mut config, &mut config_store, &mut settings);
+            brightness = settings.get_or(Key::Brightness, DEFAULT_BRIGHTNESS);
+        }
+
         let now = Instant::now();
-        let delta_time_ms = now.duration_since(last_tick).as_millis();
+        if now.duration_since(sim_time) > MAX_CATCH_UP {
+            warn!(
+                "Simulation is {}ms behind, skipping ahead",
+                now.duration_since(sim_time).as_millis()
+            );
+            sim_time = now - MAX_CATCH_UP;
+        }
+
+        while now.duration_since(sim_time) >= SIM_STEP {
+            sim_time += SIM_STEP;
 
-        if BTN_DOWN.load(core::sync::atomic::Ordering::Relaxed) {
-            game_state.button_click();
-            BTN_DOWN.store(false, core::sync::atomic::Ordering::Relaxed);
+            // Apply input in the step it happened in
+            while let Some(event) = pending_event
+                .take()
+                .or_else(|| INPUT_EVENTS.try_receive().ok())
+            {
+                if event.at > sim_time {
+                    pending_event = Some(event);
+                    break;
+                }
+                if let Err(e) = game_state.input(event, &mut highscore, &mut settings, &config) {
+                    error!("Failed to handle {event:?}: {e:?}");
+                }
+            }
+
+            game_state.update(SIM_STEP.as_millis(), &highscore, &mut settings, &config);
         }
 
-        match game_state
-            .advance(delta_time_ms, &mut highscore, &mut dot_matrix)
-            .await
-        {
-            Ok(_) => {
-                last_tick = now;
+        let alpha = Fixed::from_ratio(
+            now.duration_since(sim_time).as_micros() as i64,
+            SIM_STEP.as_micros() as i64,
+        );
+        if let Err(e) = game_state.render(alpha, brightness, &mut dot_matrix).await {
+            error!("Failed to render game state: {e:?}");
+        }
+
+        frames.next().await;
+    }
+}
+
+fn handle_console_command(
+    command: ConsoleCommand,
+    config: &mut GameConfig,
+    config_store: &mut ConfigStore,
+    settings: &mut Settings,
+) {
+    match command {
+        ConsoleCommand::Set { key, value } => match config.set(&key, value) {
+            Ok(()) => info!("{key} = {value}"),
+            Err(ConfigError::UnknownKey) => set_setting(&key, value, settings),
+            Err(e) => warn!("Failed to set {key}: {e:?}"),
+        },
+        ConsoleCommand::ShowConfig => config.log(),
+        ConsoleCommand::SaveConfig => {
+            if let Err(e) = config_store.save(config) {
+                warn!("Failed to save config: {e:?}");
             }
-            Err(e) => error!("Failed to advance game state: {e:?}"),
         }
+        ConsoleCommand::ShowStats => settings.get_or(Key::Stats, Stats::default()).log(),
+    }
+}
 
-        Timer::after(Duration::from_millis(16)).await;
+/// `set` for the settings that are stored straight away rather than with `save`
+fn set_setting(key: &str, value: f32, settings: &mut Settings) {
+    let (setting, max) = match key {
+        "brightness" => (Key::Brightness, MAX_BRIGHTNESS),
+        "volume" => (Key::Volume, MAX_VOLUME),
+        _ => {
+            warn!("Failed to set {key}: {:?}", ConfigError::UnknownKey);
+            return;
+        }
+    };
+    if value as u8 as f32 != value || value as u8 > max {
+        warn!("Failed to set {key}: {:?}", ConfigError::InvalidValue);
+        return;
+    }
+    if let Err(e) = settings.set(setting, &(value as u8)) {
+        warn!("{key} = {value} until the power goes, failed to store it: {e:?}");
+        return;
     }
+    match setting {
+        Key::Volume => info!("{key} = {value}, from the next boot"),
+        _ => info!("{key} = {value}"),
+    }
+}
+
+#[embassy_executor::task(pool_size = 2)]
+async fn button_input(button: GestureRecogniser<'static>) {
+    forward(button, &INPUT_EVENTS).await
+}
+
+#[embassy_executor::task]
+async fn encoder_input(encoder: RotaryEncoder<'static>) {
+    forward(encoder, &INPUT_EVENTS).await
+}
+
+#[embassy_executor::task]
+async fn console_input(console: Console<'static, UsbSerialJtagRx<'static, Async>>) {
+    forward(console, &INPUT_EVENTS).await
 }
 
 #[embassy_executor::task]
@@ -113,22 +228,34 @@ async fn main(spawner: Spawner) {
                 peripherals.GPIO9,
                 InputConfig::default().with_pull(Pull::Up),
             );
-            let mut highscore = HighScore::default();
+            let mut settings = Settings::default();
+            let mut highscore = HighScore::new(&mut settings);
             let mut intro_text = None;
             if button.is_low() {
                 info!("Resetting highscore");
                 intro_text = Some(" RESET HIGHSCORE");
-                highscore.set(0);
+                if let Err(e) = highscore.reset(&mut settings) {
+                    error!("Failed to reset highscore: {e:?}");
+                }
             }
+            let volume = settings.get_or(Key::Volume, MAX_VOLUME);
 
             spawner
-                .spawn(game_loop(dot_matrix, highscore, intro_text))
+                .spawn(game_loop(
+                    dot_matrix,
+                    settings,
+                    highscore,
+                    ConfigStore::default(),
+                    intro_text,
+                ))
                 .ok();
 
-            let audio_mixer = Mixer::<MIXER_CHANNELS>::new();
+            let mut audio_mixer = Mixer::<MIXER_CHANNELS>::new();
+            audio_mixer.set_volume(volume);
             let (music_tx, sfx_tx) = (audio_mixer.writers[0], audio_mixer.writers[1]);
 
             spawner.spawn(music_task(music_tx, sfx_tx)).unwrap();
+            spawner.spawn(sound_effect_task(sfx_tx)).unwrap();
 
             // Create a higher priority spawner
             let sic = SoftwareInterruptControl::new(peripherals.SW_INTERRUPT);
@@ -148,16 +275,49 @@ async fn main(spawner: Spawner) {
                 ))
                 .expect("spawn audio worker");
 
+            // Extra inputs, all feeding the same events as the boot button. GPIO8 is left alone, it
+            // is a strapping pin.
+            let pull_up = InputConfig::default().with_pull(Pull::Up);
+            // A second button for player one. GPIO10 is SDA of the devkit's I2C bus, which the game
+            // doesn't use, and the bus's pull-up keeps it high until the button is pressed.
+            spawner
+                .spawn(button_input(GestureRecogniser::new(
+                    Input::new(peripherals.GPIO10, pull_up),
+                    Player::One,
+                )))
+                .unwrap();
+            // The second player's button, for the versus mode. Not on GPIO7, which drives the
+            // devkit's LED.
+            spawner
+                .spawn(button_input(GestureRecogniser::new(
+                    Input::new(peripherals.GPIO6, pull_up),
+                    Player::Two,
+                )))
+                .unwrap();
+            spawner
+                // UART0 RX and TX, free as the console and logs go over the USB serial port
+                .spawn(encoder_input(RotaryEncoder::new(
+                    Input::new(peripherals.GPIO20, pull_up),
+                    Input::new(peripherals.GPIO21, pull_up),
+                )))
+                .unwrap();
+            let (console_rx, _) = UsbSerialJtag::new(peripherals.USB_DEVICE)
+                .into_async()
+                .split();
+            spawner
+                .spawn(console_input(Console::new(console_rx, &CONSOLE_COMMANDS)))
+                .unwrap();
+
             button.wait_for_high().await; // If highscore reset then wait for the button to be released
+            let mut button = GestureRecogniser::new(button, Player::One);
 
             info!("Starting main loop!");
             loop {
-                button.wait_for_low().await;
-                BTN_DOWN.store(true, core::sync::atomic::Ordering::Relaxed);
-                sfx::blip(sfx_tx, 440.0, 50, 0x05FF, 0).await;
-                //Timer::after_millis(50).await;
-                button.wait_for_high().await;
-                Timer::after_millis(50).await;
+                let event = button.next().await;
+                post(&INPUT_EVENTS, event);
+                if let ButtonEvent::Press = event.button {
+                    sfx::trigger(SoundEffect::Press);
+                }
             }
         }
         Err(e) => {
@@ -171,3 +331,8 @@ pub async fn music_task(music_tx: AudioProducerChannel, sfx_tx: AudioProducerCha
         pattern_demo(music_tx, sfx_tx).await;
     }
 }
+
+#[task]
+pub async fn sound_effect_task(sfx_tx: AudioProducerChannel) {
+    sfx::run_sound_effects(sfx_tx).await
+}
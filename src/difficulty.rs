@@ -0,0 +1,93 @@
+use crate::fixed::Fixed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Insane,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DifficultySettings {
+    pub x_speed: f32,
+    pub y_speed: f32,
+    /// Relative speed increase of the ball per ms
+    pub acceleration: f32,
+    pub max_health: u8,
+    /// How much of a side the pad slides in per ms after moving
+    pub pad_slide_speed: Fixed,
+}
+
+impl Difficulty {
+    pub const COUNT: usize = 4;
+    pub const ALL: [Difficulty; Self::COUNT] = [
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+        Difficulty::Insane,
+    ];
+
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Insane,
+            Difficulty::Insane => Difficulty::Easy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Insane => "Insane",
+        }
+    }
+
+    /// Where the high score for this difficulty is kept, `Normal` comes first as it is
+    /// the one that was stored before there were difficulties
+    pub fn slot(self) -> usize {
+        match self {
+            Difficulty::Normal => 0,
+            Difficulty::Easy => 1,
+            Difficulty::Hard => 2,
+            Difficulty::Insane => 3,
+        }
+    }
+
+    /// The built-in preset, `GameConfig` holds the (possibly tuned) values actually used
+    pub const fn settings(self) -> DifficultySettings {
+        match self {
+            Difficulty::Easy => DifficultySettings {
+                x_speed: 0.0042,
+                y_speed: -0.0031,
+                acceleration: 0.000006,
+                max_health: 6,
+                pad_slide_speed: Fixed::from_f32(0.012),
+            },
+            Difficulty::Normal => DifficultySettings {
+                x_speed: 0.0054,
+                y_speed: -0.004,
+                acceleration: 0.00001,
+                max_health: 4,
+                pad_slide_speed: Fixed::from_f32(0.009),
+            },
+            Difficulty::Hard => DifficultySettings {
+                x_speed: 0.0066,
+                y_speed: -0.0049,
+                acceleration: 0.000014,
+                max_health: 3,
+                pad_slide_speed: Fixed::from_f32(0.008),
+            },
+            Difficulty::Insane => DifficultySettings {
+                x_speed: 0.008,
+                y_speed: -0.006,
+                acceleration: 0.00002,
+                max_health: 2,
+                pad_slide_speed: Fixed::from_f32(0.007),
+            },
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use core::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// A Q16.16 fixed point number, the ESP32-C3 has no FPU so the physics avoids `f32`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    const FRAC_BITS: u32 = 16;
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(1 << Self::FRAC_BITS);
+    pub const HALF: Fixed = Fixed(1 << (Self::FRAC_BITS - 1));
+    pub const MAX: Fixed = Fixed(i32::MAX);
+
+    pub const fn from_int(value: i32) -> Self {
+        Self(value << Self::FRAC_BITS)
+    }
+
+    /// Only meant for constants and config values, not for every frame
+    pub const fn from_f32(value: f32) -> Self {
+        let scaled = value * Self::ONE.0 as f32;
+        Self(if scaled < 0.0 {
+            scaled - 0.5
+        } else {
+            scaled + 0.5
+        } as i32)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / Self::ONE.0 as f32
+    }
+
+    /// `numerator / denominator`, rounded
+    pub fn from_ratio(numerator: i64, denominator: i64) -> Self {
+        Self(Self::round_div(numerator << Self::FRAC_BITS, denominator))
+    }
+
+    /// Rounded towards negative infinity, like `floorf`
+    pub const fn to_int(self) -> i32 {
+        self.0 >> Self::FRAC_BITS
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    pub fn signum(self) -> Self {
+        Self::from_int(self.0.signum())
+    }
+
+    /// `self * numerator / denominator`, rounded once at the end
+    pub fn scale(self, numerator: i64, denominator: i64) -> Self {
+        Self(Self::round_div(self.0 as i64 * numerator, denominator))
+    }
+
+    /// How far a rate per second gets in `ms` milliseconds, e.g. a speed in pixels per second
+    pub fn per_ms(self, ms: i64) -> Self {
+        self.scale(ms, 1000)
+    }
+
+    /// How many `step`s it takes to cover `self`, rounded up
+    pub fn steps_of(self, step: Fixed) -> i32 {
+        (self.0 + step.0 - 1) / step.0
+    }
+
+    /// The remainder of `self / rhs`, never negative, for wrapping around
+    pub fn rem_euclid(self, rhs: Fixed) -> Self {
+        Self(self.0.rem_euclid(rhs.0))
+    }
+
+    pub fn mul_int(self, value: i32) -> Self {
+        Self(self.0 * value)
+    }
+
+    fn round_div(numerator: i64, denominator: i64) -> i32 {
+        let half = denominator.abs() / 2;
+        let rounded = if (numerator < 0) == (denominator < 0) {
+            numerator + half
+        } else {
+            numerator - half
+        };
+        (rounded / denominator) as i32
+    }
+}
+
+impl Add for Fixed {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self(Self::round_div(
+            self.0 as i64 * rhs.0 as i64,
+            Self::ONE.0 as i64,
+        ))
+    }
+}
+
+impl Div for Fixed {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        Self::from_ratio(self.0 as i64, rhs.0 as i64)
+    }
+}
@@ -6,7 +6,7 @@ const REAL_0_IDX: u8 = b'0';
 const REAL_9_IDX: u8 = b'9';
 pub const FONT_HEIGHT: usize = 6;
 pub const FONT_WIDTH: u8 = 4;
-const FONT_COUNT: usize = 39;
+const FONT_COUNT: usize = 41;
 #[rustfmt::skip]
 pub const FONT: [[u8; FONT_HEIGHT]; FONT_COUNT] = [
     [
@@ -321,6 +321,22 @@ pub const FONT: [[u8; FONT_HEIGHT]; FONT_COUNT] = [
         0b01000000,
         0b00000000,
     ],
+    [
+        0b00000000,
+        0b00000000,
+        0b11100000,
+        0b00000000,
+        0b00000000,
+        0b00000000,
+    ],
+    [
+        0b00000000,
+        0b01000000,
+        0b11100000,
+        0b01000000,
+        0b00000000,
+        0b00000000,
+    ],
 ];
 
 pub fn get_font_data(ch: &char) -> Option<&[u8; FONT_HEIGHT]> {
@@ -336,6 +352,10 @@ pub fn get_font_data(ch: &char) -> Option<&[u8; FONT_HEIGHT]> {
         return Some(&FONT[37]);
     } else if ch as u8 == b':' {
         return Some(&FONT[38]);
+    } else if ch as u8 == b'-' {
+        return Some(&FONT[39]);
+    } else if ch as u8 == b'+' {
+        return Some(&FONT[40]);
     }
     None
 }
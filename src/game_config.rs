@@ -0,0 +1,421 @@
+use core::{ops::RangeInclusive, str::FromStr};
+
+use embedded_storage::{ReadStorage, Storage};
+use esp_storage::FlashStorage;
+use heapless::{format, String};
+use log::{info, warn};
+
+use crate::{
+    difficulty::{Difficulty, DifficultySettings},
+    fixed::Fixed,
+    storage::{
+        partition::{Partition, CONFIG_SECTOR},
+        StorageError,
+    },
+};
+
+// Bump the version when keys are added, removed or reordered
+const HEADER: &[u8; 4] = b"cfg7";
+const VALUE_SIZE: usize = size_of::<f32>();
+
+const GLOBAL_KEYS: [&str; 25] = [
+    "bounce_margin",
+    "pad_thickness",
+    "pad_deflection",
+    "min_angle",
+    "max_ball_speed",
+    "orbit_pad",
+    "orbit_pad_length",
+    "orbit_pad_speed",
+    "hurt_ms",
+    "dying_ms",
+    "countdown_ms",
+    "resume_countdown_ms",
+    "intro_ticker_speed",
+    "game_over_ticker_speed",
+    "multi_ball_score",
+    "multi_ball_count",
+    "power_up_interval_ms",
+    "power_up_lifetime_ms",
+    "power_up_duration_ms",
+    "attract_idle_ms",
+    "attract_demo_ms",
+    "combo_step",
+    "max_combo_multiplier",
+    "near_miss_ms",
+    "near_miss_bonus",
+];
+const DIFFICULTY_KEYS: [&str; 5] = [
+    "x_speed",
+    "y_speed",
+    "acceleration",
+    "max_health",
+    "pad_slide_speed",
+];
+const KEY_COUNT: usize = GLOBAL_KEYS.len() + DIFFICULTY_KEYS.len() * Difficulty::COUNT;
+
+pub type Key = String<32>;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    UnknownKey,
+    InvalidValue,
+}
+
+/// All gameplay tuning in one place, so it can be changed over the console without recompiling.
+#[derive(Clone)]
+pub struct GameConfig {
+    pub difficulties: [DifficultySettings; Difficulty::COUNT],
+    /// How close the ball gets to the edge of the arena before bouncing
+    pub bounce_margin: Fixed,
+    pub pad_thickness: Fixed,
+    /// How much hitting the ball off-centre bends its path, 0 bounces like a mirror
+    pub pad_deflection: Fixed,
+    /// The smallest share of the ball's speed going either way after a pad hit, at most 0.5
+    pub min_angle: Fixed,
+    /// Pixels per second, horizontal and vertical speed added together, at least 1 so the ball
+    /// never gets slowed down to a stop
+    pub max_ball_speed: Fixed,
+    /// Play with a short pad going round the edge instead of one jumping between whole sides
+    pub orbit_pad: bool,
+    pub orbit_pad_length: u8,
+    /// Pixels per second
+    pub orbit_pad_speed: Fixed,
+    pub hurt_ms: i64,
+    pub dying_ms: i64,
+    pub countdown_ms: i64,
+    pub resume_countdown_ms: i64,
+    pub intro_ticker_speed: f32,
+    pub game_over_ticker_speed: f32,
+    /// Extra balls are added every time the score passes another multiple of this
+    pub multi_ball_score: u8,
+    pub multi_ball_count: u8,
+    pub power_up_interval_ms: i64,
+    /// How long a pickup stays in the arena before disappearing
+    pub power_up_lifetime_ms: i64,
+    /// How long timed effects like slow motion last
+    pub power_up_duration_ms: i64,
+    /// How long the intro waits for a button press before playing a demo game
+    pub attract_idle_ms: i64,
+    /// The longest a demo game goes on before going back to the intro
+    pub attract_demo_ms: i64,
+    /// Bounces in a row without pad damage for the score multiplier to go up by one
+    pub combo_step: u8,
+    pub max_combo_multiplier: u8,
+    /// How close to hitting the pad a ball has to be when the pad turns away for a near miss
+    pub near_miss_ms: i64,
+    pub near_miss_bonus: u8,
+}
+
+/// Finds the field behind `$key` in `$config` with the range it has to be in, and turns it into
+/// `$make!(Kind, place, range)`. Both `GameConfig::get` and `GameConfig::field` go through it, so
+/// the keys are only listed once whether the config is borrowed mutably or not.
+macro_rules! find_field {
+    ($config:expr, $key:expr, $make:ident) => {{
+        if let Some((prefix, key)) = $key.split_once('.') {
+            let difficulty = Difficulty::ALL
+                .into_iter()
+                .find(|difficulty| difficulty.name().eq_ignore_ascii_case(prefix))?
+                as usize;
+            return match key {
+                "x_speed" => $make!(
+                    Float,
+                    $config.difficulties[difficulty].x_speed,
+                    -0.05..=0.05
+                ),
+                "y_speed" => $make!(
+                    Float,
+                    $config.difficulties[difficulty].y_speed,
+                    -0.05..=0.05
+                ),
+                "acceleration" => $make!(
+                    Float,
+                    $config.difficulties[difficulty].acceleration,
+                    0.0..=0.001
+                ),
+                "max_health" => $make!(Count, $config.difficulties[difficulty].max_health, 1..=99),
+                "pad_slide_speed" => $make!(
+                    Fixed,
+                    $config.difficulties[difficulty].pad_slide_speed,
+                    0.0..=1.0
+                ),
+                _ => None,
+            };
+        }
+
+        match $key {
+            "bounce_margin" => $make!(Fixed, $config.bounce_margin, 0.0..=1.0),
+            "pad_thickness" => $make!(Fixed, $config.pad_thickness, 0.0..=2.0),
+            "pad_deflection" => $make!(Fixed, $config.pad_deflection, 0.0..=1.0),
+            "min_angle" => $make!(Fixed, $config.min_angle, 0.0..=0.5),
+            "max_ball_speed" => $make!(Fixed, $config.max_ball_speed, 1.0..=64.0),
+            "orbit_pad" => $make!(Switch, $config.orbit_pad),
+            "orbit_pad_length" => $make!(Count, $config.orbit_pad_length, 1..=27),
+            "orbit_pad_speed" => $make!(Fixed, $config.orbit_pad_speed, 0.0..=64.0),
+            "hurt_ms" => $make!(Millis, $config.hurt_ms, 0..=10_000),
+            "dying_ms" => $make!(Millis, $config.dying_ms, 0..=10_000),
+            "countdown_ms" => $make!(Millis, $config.countdown_ms, 0..=9_000),
+            "resume_countdown_ms" => $make!(Millis, $config.resume_countdown_ms, 0..=9_000),
+            "intro_ticker_speed" => $make!(Float, $config.intro_ticker_speed, 0.0..=0.1),
+            "game_over_ticker_speed" => {
+                $make!(Float, $config.game_over_ticker_speed, 0.0..=0.1)
+            }
+            "multi_ball_score" => $make!(Count, $config.multi_ball_score, 1..=u8::MAX),
+            "multi_ball_count" => $make!(Count, $config.multi_ball_count, 1..=u8::MAX),
+            "power_up_interval_ms" => {
+                $make!(Millis, $config.power_up_interval_ms, 0..=600_000)
+            }
+            "power_up_lifetime_ms" => {
+                $make!(Millis, $config.power_up_lifetime_ms, 0..=600_000)
+            }
+            "power_up_duration_ms" => {
+                $make!(Millis, $config.power_up_duration_ms, 0..=600_000)
+            }
+            "attract_idle_ms" => $make!(Millis, $config.attract_idle_ms, 0..=600_000),
+            "attract_demo_ms" => $make!(Millis, $config.attract_demo_ms, 0..=600_000),
+            "combo_step" => $make!(Count, $config.combo_step, 1..=u8::MAX),
+            "max_combo_multiplier" => {
+                $make!(Count, $config.max_combo_multiplier, 1..=u8::MAX)
+            }
+            "near_miss_ms" => $make!(Millis, $config.near_miss_ms, 0..=10_000),
+            "near_miss_bonus" => $make!(Count, $config.near_miss_bonus, 1..=u8::MAX),
+            _ => None,
+        }
+    }};
+}
+
+/// A mutable `Field`, for setting the value
+macro_rules! field {
+    ($kind:ident, $place:expr $(, $range:expr)?) => {
+        Some(Field::$kind(&mut $place $(, $range)?))
+    };
+}
+
+/// The value of a field as an `f32`, from a copy of just that field
+macro_rules! value {
+    ($kind:ident, $place:expr $(, $range:expr)?) => {{
+        let mut value = $place;
+        Some(Field::$kind(&mut value $(, $range)?).get())
+    }};
+}
+
+/// A value that can be set over the console, with the range it has to be in for the game to keep
+/// working
+enum Field<'a> {
+    Float(&'a mut f32, RangeInclusive<f32>),
+    Fixed(&'a mut Fixed, RangeInclusive<f32>),
+    Millis(&'a mut i64, RangeInclusive<i64>),
+    Count(&'a mut u8, RangeInclusive<u8>),
+    /// 0 or 1
+    Switch(&'a mut bool),
+}
+
+impl Field<'_> {
+    fn get(&self) -> f32 {
+        match self {
+            Field::Float(value, _) => **value,
+            Field::Fixed(value, _) => value.to_f32(),
+            Field::Millis(value, _) => **value as f32,
+            Field::Count(value, _) => **value as f32,
+            Field::Switch(value) => **value as u8 as f32,
+        }
+    }
+
+    fn set(&mut self, value: f32) -> Result<(), ConfigError> {
+        if !value.is_finite() {
+            return Err(ConfigError::InvalidValue);
+        }
+        match self {
+            Field::Float(field, range) if range.contains(&value) => **field = value,
+            Field::Fixed(field, range) if range.contains(&value) => {
+                **field = Fixed::from_f32(value)
+            }
+            Field::Millis(field, range) if range.contains(&(value as i64)) => {
+                **field = value as i64
+            }
+            Field::Count(field, range)
+                if (0.0..=u8::MAX as f32).contains(&value) && range.contains(&(value as u8)) =>
+            {
+                **field = value as u8
+            }
+            Field::Switch(field) if value == 0.0 || value == 1.0 => **field = value == 1.0,
+            _ => return Err(ConfigError::InvalidValue),
+        }
+        Ok(())
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            difficulties: Difficulty::ALL.map(Difficulty::settings),
+            bounce_margin: Fixed::from_f32(0.5),
+            pad_thickness: Fixed::ONE,
+            pad_deflection: Fixed::from_f32(0.4),
+            min_angle: Fixed::from_f32(0.2),
+            max_ball_speed: Fixed::from_int(24),
+            orbit_pad: false,
+            orbit_pad_length: 3,
+            orbit_pad_speed: Fixed::from_int(6),
+            hurt_ms: 16 * 10,
+            dying_ms: 16 * 70,
+            countdown_ms: 3000,
+            resume_countdown_ms: 2000,
+            intro_ticker_speed: 0.008,
+            game_over_ticker_speed: 0.014,
+            multi_ball_score: 20,
+            multi_ball_count: 1,
+            power_up_interval_ms: 7000,
+            power_up_lifetime_ms: 4000,
+            power_up_duration_ms: 8000,
+            attract_idle_ms: 15000,
+            attract_demo_ms: 30000,
+            combo_step: 5,
+            max_combo_multiplier: 4,
+            near_miss_ms: 250,
+            near_miss_bonus: 5,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn difficulty(&self, difficulty: Difficulty) -> &DifficultySettings {
+        &self.difficulties[difficulty as usize]
+    }
+
+    /// Every key, in the order they are stored in flash, e.g. `countdown_ms` or `hard.x_speed`
+    pub fn keys() -> impl Iterator<Item = Key> {
+        let global = GLOBAL_KEYS
+            .iter()
+            .map(|key| Key::from_str(key).expect("a short key"));
+        let per_difficulty = Difficulty::ALL.into_iter().flat_map(|difficulty| {
+            DIFFICULTY_KEYS.iter().map(move |key| {
+                let mut name = Key::from_str(difficulty.name()).expect("a short name");
+                name.make_ascii_lowercase();
+                format!("{name}.{key}").expect("a short key")
+            })
+        });
+        global.chain(per_difficulty)
+    }
+
+    fn field(&mut self, key: &str) -> Option<Field<'_>> {
+        find_field!(self, key, field)
+    }
+
+    pub fn get(&self, key: &str) -> Option<f32> {
+        find_field!(self, key, value)
+    }
+
+    pub fn set(&mut self, key: &str, value: f32) -> Result<(), ConfigError> {
+        self.field(key).ok_or(ConfigError::UnknownKey)?.set(value)
+    }
+
+    pub fn log(&self) {
+        for key in Self::keys() {
+            info!("{key} = {}", self.get(&key).expect("a known key"));
+        }
+    }
+}
+
+/// Keeps a `GameConfig` in flash, stored as one `f32` per key in `GameConfig::keys` order
+pub struct ConfigStore {
+    flash_storage: FlashStorage,
+    /// Why there is nowhere to save the config, if so
+    addr: Result<u32, StorageError>,
+}
+
+impl ConfigStore {
+    /// The stored config, or the defaults if there is none or it can't be read
+    pub fn load(&mut self) -> GameConfig {
+        match self.read() {
+            Ok(Some(config)) => config,
+            Ok(None) => {
+                info!("No stored config, using defaults");
+                GameConfig::default()
+            }
+            Err(e) => {
+                warn!("Failed to read config, using defaults: {e:?}");
+                GameConfig::default()
+            }
+        }
+    }
+
+    fn read(&mut self) -> Result<Option<GameConfig>, StorageError> {
+        let addr = self.addr?;
+        let header = &mut [0_u8; HEADER.len()];
+        self.flash_storage
+            .read(addr, header)
+            .map_err(StorageError::flash)?;
+        if header != HEADER {
+            return Ok(None);
+        }
+
+        let mut config = GameConfig::default();
+        let values = &mut [0_u8; VALUE_SIZE * KEY_COUNT];
+        self.flash_storage
+            .read(addr + HEADER.len() as u32, values)
+            .map_err(StorageError::flash)?;
+        for (key, bytes) in GameConfig::keys().zip(values.chunks_exact(VALUE_SIZE)) {
+            let value = f32::from_be_bytes(bytes.try_into().expect("an f32"));
+            if let Err(e) = config.set(&key, value) {
+                warn!("Ignoring stored {key} = {value}: {e:?}");
+            }
+        }
+        Ok(Some(config))
+    }
+
+    pub fn save(&mut self, config: &GameConfig) -> Result<(), StorageError> {
+        let addr = self.addr?;
+        let mut bytes = [0_u8; HEADER.len() + VALUE_SIZE * KEY_COUNT];
+        bytes[..HEADER.len()].copy_from_slice(HEADER);
+        for (key, chunk) in
+            GameConfig::keys().zip(bytes[HEADER.len()..].chunks_exact_mut(VALUE_SIZE))
+        {
+            chunk.copy_from_slice(&config.get(&key).expect("a known key").to_be_bytes());
+        }
+        self.flash_storage
+            .write(addr, &bytes)
+            .map_err(StorageError::flash)?;
+        info!("Config saved");
+        Ok(())
+    }
+}
+
+impl Default for ConfigStore {
+    fn default() -> Self {
+        let mut flash_storage = FlashStorage::new();
+        let addr = Partition::find_game(&mut flash_storage)
+            .map(|partition| partition.sector(CONFIG_SECTOR))
+            .map_err(StorageError::Partition);
+        if let Err(e) = addr {
+            warn!("Nowhere to save the config, flash with partitions.csv: {e:?}");
+        }
+        Self {
+            flash_storage,
+            addr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_in_range() {
+        let mut config = GameConfig::default();
+        for key in GameConfig::keys() {
+            let value = config.get(&key).unwrap();
+            assert!(config.set(&key, value).is_ok(), "{key} = {value}");
+        }
+    }
+
+    #[test]
+    fn rejects_ball_speed_cap_of_zero() {
+        let mut config = GameConfig::default();
+        for value in [0.0, -1.0, f32::NAN] {
+            assert!(config.set("max_ball_speed", value).is_err(), "{value}");
+        }
+        assert_eq!(config.max_ball_speed, GameConfig::default().max_ball_speed);
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{
+    game_config::GameConfig,
+    pad::{Pad, PadPosition},
+};
+
+use super::{Game, Round};
+
+/// How often the autopilot looks at the arena, so it reacts like a person rather than instantly
+const THINK_MS: i64 = 120;
+
+/// Plays the pad game on its own for the attract mode: it predicts which sides the most urgent ball
+/// will hit and clicks the pad round until it is out of the way, or turns the orbiting pad back
+/// when it is heading into the ball
+#[derive(Clone, Default)]
+pub struct Autopilot {
+    next_think_ms: i64,
+}
+
+impl Autopilot {
+    pub fn update(&mut self, round: &mut Round, delta_time_ms: u64, config: &GameConfig) {
+        self.next_think_ms -= delta_time_ms as i64;
+        if self.next_think_ms > 0 {
+            return;
+        }
+        self.next_think_ms = THINK_MS;
+
+        let Some(ball) = round.balls.iter().min_by_key(|ball| ball.time_to_impact()) else {
+            return;
+        };
+        let Pad::Alive { position, .. } = &round.pad else {
+            return;
+        };
+        if let PadPosition::Orbit { .. } = position {
+            // Pressing only turns the pad back, so only press when that takes it out of the way
+            let (side, along) = ball.impact();
+            if position.approaching(side, along) {
+                round.press(config);
+            }
+            return;
+        }
+        // A ball heads for two of the four sides, so at most two clicks find a free one
+        let heading = ball.heading();
+        for _ in 0..2 {
+            match &round.pad {
+                Pad::Alive { position, .. } if heading.contains(&position.side()) => {
+                    round.press(config);
+                }
+                _ => break,
+            }
+        }
+    }
+}
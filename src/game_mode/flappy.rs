@@ -0,0 +1,145 @@
+use crate::{dot_matrix::DotMatrix, fixed::Fixed, game_config::GameConfig, rng::Rng};
+
+use super::Game;
+
+const BIRD_X: u8 = 1;
+const FLOOR: Fixed = Fixed::from_int(8);
+/// Pixels per second per second
+const GRAVITY: Fixed = Fixed::from_int(40);
+/// Pixels per second, negative is up
+const FLAP_SPEED: Fixed = Fixed::from_int(-11);
+const GAP_SIZE: u32 = 3;
+/// Columns from one pipe to the next
+const PIPE_SPACING: u8 = 4;
+const START_SCROLL_MS: i64 = 220;
+const FASTEST_SCROLL_MS: i64 = 100;
+/// How much faster the pipes come with every pipe passed
+const SCROLL_SPEEDUP_MS: i64 = 5;
+const CRASH_MS: i64 = 800;
+const CRASH_BLINK_MS: i64 = 100;
+
+/// Flap through the gaps in the pipes scrolling in from the right
+#[derive(Clone)]
+pub struct Flappy {
+    rng: Rng,
+    y: Fixed,
+    previous_y: Fixed,
+    speed: Fixed,
+    /// Which rows of each column are pipe, bit `y` set for a pipe at `y`
+    columns: [u8; 8],
+    next_scroll_ms: i64,
+    columns_to_next_pipe: u8,
+    score: u32,
+    crashed_ms: Option<i64>,
+}
+
+impl Flappy {
+    pub fn new() -> Self {
+        Self {
+            rng: Rng::from_time(),
+            y: Fixed::from_int(3),
+            previous_y: Fixed::from_int(3),
+            speed: Fixed::ZERO,
+            columns: [0; 8],
+            next_scroll_ms: START_SCROLL_MS,
+            columns_to_next_pipe: PIPE_SPACING,
+            score: 0,
+            crashed_ms: None,
+        }
+    }
+
+    fn scroll(&mut self) {
+        self.columns.rotate_left(1);
+        // The pipe that was in the bird's column has moved past it
+        if self.columns[BIRD_X as usize - 1] != 0 {
+            self.score += 1;
+        }
+
+        self.columns_to_next_pipe -= 1;
+        self.columns[7] = if self.columns_to_next_pipe == 0 {
+            self.columns_to_next_pipe = PIPE_SPACING;
+            let gap = self.rng.range(1..8 - GAP_SIZE);
+            !(((1 << GAP_SIZE) - 1) << gap) as u8
+        } else {
+            0
+        };
+    }
+
+    fn bird_hits_pipe(&self) -> bool {
+        self.columns[BIRD_X as usize] & (1 << self.y.to_int()) != 0
+    }
+}
+
+impl Default for Flappy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for Flappy {
+    fn press(&mut self, _config: &GameConfig) {
+        if self.crashed_ms.is_none() {
+            self.speed = FLAP_SPEED;
+        }
+    }
+
+    fn update(&mut self, delta_time_ms: u64, _config: &GameConfig) {
+        let delta_time_ms = delta_time_ms as i64;
+        if let Some(crashed_ms) = &mut self.crashed_ms {
+            *crashed_ms += delta_time_ms;
+            return;
+        }
+
+        self.previous_y = self.y;
+        self.speed += GRAVITY.per_ms(delta_time_ms);
+        self.y += self.speed.per_ms(delta_time_ms);
+        if self.y < Fixed::ZERO {
+            // Bump into the top and start falling
+            self.y = Fixed::ZERO;
+            self.speed = Fixed::ZERO;
+        }
+        if self.y >= FLOOR {
+            self.y = FLOOR - Fixed::ONE;
+            self.crashed_ms = Some(0);
+            return;
+        }
+
+        self.next_scroll_ms -= delta_time_ms;
+        if self.next_scroll_ms <= 0 {
+            self.next_scroll_ms +=
+                (START_SCROLL_MS - self.score as i64 * SCROLL_SPEEDUP_MS).max(FASTEST_SCROLL_MS);
+            self.scroll();
+        }
+
+        if self.bird_hits_pipe() {
+            self.crashed_ms = Some(0);
+        }
+    }
+
+    fn draw(&self, alpha: Fixed, dot_matrix: &mut DotMatrix<'_>) {
+        for (x, column) in self.columns.iter().enumerate() {
+            for y in 0..8 {
+                if column & (1 << y) != 0 {
+                    dot_matrix.put(x as u8, y);
+                }
+            }
+        }
+
+        let blink_off = self
+            .crashed_ms
+            .is_some_and(|crashed_ms| (crashed_ms / CRASH_BLINK_MS) % 2 == 1);
+        if !blink_off {
+            let y = self.previous_y + (self.y - self.previous_y) * alpha;
+            dot_matrix.put(BIRD_X, y.to_int().clamp(0, 7) as u8);
+        }
+    }
+
+    fn score(&self) -> u32 {
+        self.score
+    }
+
+    fn is_over(&self) -> bool {
+        self.crashed_ms
+            .is_some_and(|crashed_ms| crashed_ms >= CRASH_MS)
+    }
+}
@@ -0,0 +1,240 @@
+mod autopilot;
+mod flappy;
+mod pads;
+mod reaction;
+mod snake;
+mod versus;
+
+pub use autopilot::Autopilot;
+pub use flappy::Flappy;
+pub use pads::Round;
+pub use reaction::Reaction;
+pub use snake::Snake;
+pub use versus::Versus;
+
+use heapless::String;
+
+use crate::{
+    difficulty::Difficulty,
+    dot_matrix::DotMatrix,
+    fixed::Fixed,
+    game_config::GameConfig,
+    input::Player,
+    scoring::{Scoring, BREAKDOWN_SIZE},
+    storage::settings::Value,
+};
+
+/// A one-button game that `GameState` can start, pause and end
+pub trait Game {
+    /// A press of the button, this is the only input a game gets
+    fn press(&mut self, config: &GameConfig);
+    /// A press from either player's button, single player games only listen to the first one
+    fn press_by(&mut self, player: Player, config: &GameConfig) {
+        if player == Player::One {
+            self.press(config);
+        }
+    }
+    fn update(&mut self, delta_time_ms: u64, config: &GameConfig);
+    fn draw(&self, alpha: Fixed, dot_matrix: &mut DotMatrix<'_>);
+    fn score(&self) -> u32;
+    /// Done playing, time for the game over screen
+    fn is_over(&self) -> bool;
+    /// Whether the display should be dimmed, e.g. during slow motion
+    fn dimmed(&self) -> bool {
+        false
+    }
+    /// What the game over screen says, for games where the score alone doesn't tell
+    fn result(&self) -> Option<String<32>> {
+        None
+    }
+    /// Where the score came from, shown after it on the game over screen
+    fn breakdown(&self) -> Option<String<BREAKDOWN_SIZE>> {
+        None
+    }
+    /// The pad game's wall hits, rallies and damage, for the lifetime stats
+    fn scoring(&self) -> Option<&Scoring> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    Pads,
+    Flappy,
+    Reaction,
+    Snake,
+    /// Two players on two buttons
+    Versus,
+}
+
+impl GameMode {
+    pub const COUNT: usize = 5;
+    pub const ALL: [GameMode; Self::COUNT] = [
+        GameMode::Pads,
+        GameMode::Flappy,
+        GameMode::Reaction,
+        GameMode::Snake,
+        GameMode::Versus,
+    ];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::COUNT]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Pads => "Pads",
+            GameMode::Flappy => "Flappy",
+            GameMode::Reaction => "Reaction",
+            GameMode::Snake => "Snake",
+            GameMode::Versus => "Versus",
+        }
+    }
+}
+
+/// What has been picked in the intro menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub mode: GameMode,
+    /// Only the pad game has difficulty levels
+    pub difficulty: Difficulty,
+}
+
+impl Selection {
+    /// Where the best score is kept in `HighScore`, the pad game has one per difficulty and the
+    /// other single player modes come after those
+    pub fn highscore_slot(self) -> Option<usize> {
+        match self.mode {
+            GameMode::Pads => Some(self.difficulty.slot()),
+            GameMode::Flappy => Some(Difficulty::COUNT),
+            GameMode::Reaction => Some(Difficulty::COUNT + 1),
+            GameMode::Snake => Some(Difficulty::COUNT + 2),
+            GameMode::Versus => None,
+        }
+    }
+}
+
+/// The mode then the difficulty
+impl Value for Selection {
+    const SIZE: usize = 2;
+
+    fn to_bytes(&self, bytes: &mut [u8]) {
+        bytes[0] = self.mode as u8;
+        bytes[1] = self.difficulty as u8;
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self {
+            mode: *GameMode::ALL.get(bytes[0] as usize)?,
+            difficulty: *Difficulty::ALL.get(bytes[1] as usize)?,
+        })
+    }
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::Pads,
+            difficulty: Difficulty::Normal,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum AnyGame {
+    Pads(Round),
+    Flappy(Flappy),
+    Reaction(Reaction),
+    Snake(Snake),
+    Versus(Versus),
+}
+
+/// The game being played, whichever mode it is
+#[derive(Clone)]
+pub struct Session {
+    game: AnyGame,
+    /// Time spent playing so far, not counting pauses
+    played_ms: u64,
+}
+
+impl Session {
+    pub fn new(selection: Selection, config: &GameConfig) -> Self {
+        let game = match selection.mode {
+            GameMode::Pads => AnyGame::Pads(Round::new(selection.difficulty, config)),
+            GameMode::Flappy => AnyGame::Flappy(Flappy::new()),
+            GameMode::Reaction => AnyGame::Reaction(Reaction::new()),
+            GameMode::Snake => AnyGame::Snake(Snake::new()),
+            GameMode::Versus => AnyGame::Versus(Versus::new()),
+        };
+        Self { game, played_ms: 0 }
+    }
+
+    pub fn played_ms(&self) -> u64 {
+        self.played_ms
+    }
+
+    fn game(&self) -> &dyn Game {
+        match &self.game {
+            AnyGame::Pads(round) => round,
+            AnyGame::Flappy(flappy) => flappy,
+            AnyGame::Reaction(reaction) => reaction,
+            AnyGame::Snake(snake) => snake,
+            AnyGame::Versus(versus) => versus,
+        }
+    }
+
+    fn game_mut(&mut self) -> &mut dyn Game {
+        match &mut self.game {
+            AnyGame::Pads(round) => round,
+            AnyGame::Flappy(flappy) => flappy,
+            AnyGame::Reaction(reaction) => reaction,
+            AnyGame::Snake(snake) => snake,
+            AnyGame::Versus(versus) => versus,
+        }
+    }
+}
+
+impl Game for Session {
+    fn press(&mut self, config: &GameConfig) {
+        self.game_mut().press(config);
+    }
+
+    fn press_by(&mut self, player: Player, config: &GameConfig) {
+        self.game_mut().press_by(player, config);
+    }
+
+    fn update(&mut self, delta_time_ms: u64, config: &GameConfig) {
+        if !self.is_over() {
+            self.played_ms += delta_time_ms;
+        }
+        self.game_mut().update(delta_time_ms, config);
+    }
+
+    fn draw(&self, alpha: Fixed, dot_matrix: &mut DotMatrix<'_>) {
+        self.game().draw(alpha, dot_matrix);
+    }
+
+    fn score(&self) -> u32 {
+        self.game().score()
+    }
+
+    fn is_over(&self) -> bool {
+        self.game().is_over()
+    }
+
+    fn dimmed(&self) -> bool {
+        self.game().dimmed()
+    }
+
+    fn result(&self) -> Option<String<32>> {
+        self.game().result()
+    }
+
+    fn breakdown(&self) -> Option<String<BREAKDOWN_SIZE>> {
+        self.game().breakdown()
+    }
+
+    fn scoring(&self) -> Option<&Scoring> {
+        self.game().scoring()
+    }
+}
@@ -0,0 +1,177 @@
+use heapless::{String, Vec};
+use log::info;
+
+use crate::{
+    audio::sfx::{self, SoundEffect},
+    ball::{Ball, Bounce},
+    difficulty::Difficulty,
+    dot_matrix::DotMatrix,
+    fixed::Fixed,
+    game_config::GameConfig,
+    level::Level,
+    pad::{Pad, PadPosition},
+    power_up::{PowerUpKind, PowerUps},
+    scoring::{Scoring, BREAKDOWN_SIZE},
+};
+
+use super::Game;
+
+const MAX_BALLS: usize = 4;
+
+/// The original game: keep the balls off the pad by turning it away to another side
+#[derive(Clone)]
+pub struct Round {
+    pub balls: Vec<Ball, MAX_BALLS>,
+    pub pad: Pad,
+    pub scoring: Scoring,
+    pub difficulty: Difficulty,
+    pub power_ups: PowerUps,
+    pub level: Level,
+    next_multi_ball: u32,
+}
+
+impl Round {
+    pub fn new(difficulty: Difficulty, config: &GameConfig) -> Self {
+        let settings = config.difficulty(difficulty);
+        let mut balls = Vec::new();
+        balls.push(Ball::new(3, 3, settings)).ok();
+        Self {
+            balls,
+            pad: Pad::new(PadPosition::start(config), settings.max_health),
+            scoring: Scoring::default(),
+            difficulty,
+            power_ups: PowerUps::new(config),
+            level: Level::new(0),
+            next_multi_ball: config.multi_ball_score as u32,
+        }
+    }
+
+    fn advance(&mut self, delta_time_ms: u64, config: &GameConfig) {
+        let Round {
+            balls,
+            pad,
+            scoring,
+            power_ups,
+            level,
+            ..
+        } = self;
+        let settings = config.difficulty(self.difficulty);
+        pad.update(delta_time_ms, settings.pad_slide_speed);
+        power_ups.update(delta_time_ms, config);
+        let ball_time_ms = power_ups.ball_time(delta_time_ms);
+
+        // Every ball moves against the same pad, so several balls hitting it in one tick only hurt once
+        let mut pad_hit = false;
+        let mut i = 0;
+        while i < balls.len() {
+            let ball = &mut balls[i];
+            if let Some(PowerUpKind::Health) =
+                power_ups.collect(ball.x.to_int() as u8, ball.y.to_int() as u8, config)
+            {
+                pad.heal(settings.max_health);
+            }
+            match ball.update(pad, level, ball_time_ms, config) {
+                Some(Bounce::Pad) => {
+                    pad_hit = true;
+                    // Extra balls are lost when they hit the pad, the last one keeps bouncing
+                    if balls.len() > 1 {
+                        balls.swap_remove(i);
+                        continue;
+                    }
+                }
+                Some(Bounce::Wall) => scoring.bounce(power_ups.multiplier(), false, config),
+                Some(Bounce::Brick) => {
+                    sfx::trigger(SoundEffect::Brick);
+                    scoring.bounce(power_ups.multiplier(), true, config);
+                }
+                None => {}
+            }
+            i += 1;
+        }
+        if pad_hit && !power_ups.use_shield() {
+            pad.take_damage(config);
+            scoring.damage();
+        }
+
+        if level.cleared() {
+            info!("Level {} cleared", level.number);
+            *level = Level::new(level.number + 1);
+            sfx::trigger(SoundEffect::LevelCleared);
+            for ball in balls.iter() {
+                level.clear(ball.x.to_int() as u8, ball.y.to_int() as u8);
+            }
+        }
+
+        while scoring.total() >= self.next_multi_ball {
+            self.next_multi_ball += config.multi_ball_score as u32;
+            info!("Multi-ball!");
+            for _ in 0..config.multi_ball_count {
+                let ball = balls[0].split();
+                if balls.push(ball).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn draw_arena(&self, alpha: Fixed, dot_matrix: &mut DotMatrix<'_>) {
+        self.level.draw(dot_matrix);
+        if !self.power_ups.hide_pad() {
+            self.pad.draw(dot_matrix);
+        }
+        self.power_ups.draw(dot_matrix);
+        for ball in &self.balls {
+            ball.draw(alpha, dot_matrix);
+            if self.power_ups.multiplier() > 1 {
+                ball.draw_trail(dot_matrix);
+            }
+        }
+    }
+}
+
+impl Game for Round {
+    fn press(&mut self, config: &GameConfig) {
+        if let Pad::Alive { position, .. } = &mut self.pad {
+            let side = position.side();
+            let near_miss = Fixed::from_ratio(config.near_miss_ms, 1000);
+            if self
+                .balls
+                .iter()
+                .any(|ball| ball.time_to_pad(side, config) < near_miss)
+            {
+                self.scoring.dodge();
+            }
+            position.next();
+        }
+    }
+
+    fn update(&mut self, delta_time_ms: u64, config: &GameConfig) {
+        if !self.is_over() {
+            self.advance(delta_time_ms, config);
+        }
+    }
+
+    fn draw(&self, alpha: Fixed, dot_matrix: &mut DotMatrix<'_>) {
+        self.draw_arena(alpha, dot_matrix);
+    }
+
+    fn score(&self) -> u32 {
+        self.scoring.total()
+    }
+
+    fn breakdown(&self) -> Option<String<BREAKDOWN_SIZE>> {
+        Some(self.scoring.breakdown())
+    }
+
+    fn scoring(&self) -> Option<&Scoring> {
+        Some(&self.scoring)
+    }
+
+    fn is_over(&self) -> bool {
+        matches!(self.pad, Pad::Dead)
+    }
+
+    fn dimmed(&self) -> bool {
+        self.power_ups.slow_motion()
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{dot_matrix::DotMatrix, fixed::Fixed, game_config::GameConfig, rng::Rng};
+
+use super::Game;
+
+const ROUNDS: u8 = 5;
+/// Random wait before the display lights up, so it can't be timed
+const WAIT_MS: core::ops::Range<u32> = 1000..4000;
+/// Pressing later than this scores nothing
+const TOO_SLOW_MS: i64 = 1000;
+const RESULT_MS: i64 = 1000;
+const BLINK_MS: i64 = 120;
+
+#[rustfmt::skip]
+const CROSS_BITMAP: [u8; 8] = [
+    0b10000001,
+    0b01000010,
+    0b00100100,
+    0b00011000,
+    0b00011000,
+    0b00100100,
+    0b01000010,
+    0b10000001,
+];
+
+#[derive(Clone, Copy)]
+enum Phase {
+    /// Dark until the timer runs out
+    Waiting(i64),
+    /// Lit up for this long, waiting for the press
+    Go(i64),
+    /// Showing how well the last round went
+    Result { remaining_ms: i64, points: u32 },
+    /// Pressed before the display lit up, which ends the game
+    FalseStart(i64),
+}
+
+/// Press as soon as the display lights up, a few rounds in a row. Faster is more points.
+#[derive(Clone)]
+pub struct Reaction {
+    rng: Rng,
+    phase: Phase,
+    round: u8,
+    score: u32,
+    over: bool,
+}
+
+impl Reaction {
+    pub fn new() -> Self {
+        let mut rng = Rng::from_time();
+        Self {
+            phase: Phase::Waiting(rng.range(WAIT_MS) as i64),
+            rng,
+            round: 0,
+            score: 0,
+            over: false,
+        }
+    }
+
+    fn points(reaction_ms: i64) -> u32 {
+        ((TOO_SLOW_MS - reaction_ms).max(0) / 10) as u32
+    }
+
+    fn show_result(&mut self, points: u32) {
+        self.score += points;
+        self.phase = Phase::Result {
+            remaining_ms: RESULT_MS,
+            points,
+        };
+    }
+}
+
+impl Default for Reaction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for Reaction {
+    fn press(&mut self, _config: &GameConfig) {
+        match self.phase {
+            Phase::Waiting(_) => self.phase = Phase::FalseStart(RESULT_MS),
+            Phase::Go(reaction_ms) => self.show_result(Self::points(reaction_ms)),
+            Phase::Result { .. } | Phase::FalseStart(_) => {}
+        }
+    }
+
+    fn update(&mut self, delta_time_ms: u64, _config: &GameConfig) {
+        let delta_time_ms = delta_time_ms as i64;
+        match &mut self.phase {
+            Phase::Waiting(remaining_ms) => {
+                *remaining_ms -= delta_time_ms;
+                if *remaining_ms <= 0 {
+                    self.phase = Phase::Go(0);
+                }
+            }
+            Phase::Go(reaction_ms) => {
+                *reaction_ms += delta_time_ms;
+                if *reaction_ms >= TOO_SLOW_MS {
+                    self.show_result(0);
+                }
+            }
+            Phase::Result { remaining_ms, .. } => {
+                *remaining_ms -= delta_time_ms;
+                if *remaining_ms <= 0 {
+                    self.round += 1;
+                    if self.round == ROUNDS {
+                        self.over = true;
+                    } else {
+                        self.phase = Phase::Waiting(self.rng.range(WAIT_MS) as i64);
+                    }
+                }
+            }
+            Phase::FalseStart(remaining_ms) => {
+                *remaining_ms -= delta_time_ms;
+                if *remaining_ms <= 0 {
+                    self.over = true;
+                }
+            }
+        }
+    }
+
+    fn draw(&self, _alpha: Fixed, dot_matrix: &mut DotMatrix<'_>) {
+        match self.phase {
+            Phase::Waiting(_) => {
+                for (x, y) in [(3, 3), (4, 3), (3, 4), (4, 4)] {
+                    dot_matrix.put(x, y);
+                }
+            }
+            Phase::Go(_) => dot_matrix.fill(),
+            Phase::Result { points, .. } => {
+                // A bar growing up from the bottom, full for an instant press
+                let rows = (points * 8).div_ceil(Self::points(0)) as u8;
+                for y in 8 - rows..8 {
+                    dot_matrix.set_row(y, 0b11111111);
+                }
+            }
+            Phase::FalseStart(remaining_ms) => {
+                if (remaining_ms / BLINK_MS) % 2 == 0 {
+                    dot_matrix.draw(&CROSS_BITMAP);
+                }
+            }
+        }
+    }
+
+    fn score(&self) -> u32 {
+        self.score
+    }
+
+    fn is_over(&self) -> bool {
+        self.over
+    }
+}
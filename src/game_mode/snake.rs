@@ -0,0 +1,166 @@
+use heapless::Deque;
+
+use crate::{dot_matrix::DotMatrix, fixed::Fixed, game_config::GameConfig, rng::Rng};
+
+use super::Game;
+
+/// One cell is always left free for the food
+const MAX_LENGTH: usize = 63;
+const START_STEP_MS: i64 = 400;
+const FASTEST_STEP_MS: i64 = 120;
+/// How much faster the snake gets for every food eaten
+const STEP_SPEEDUP_MS: i64 = 10;
+/// Presses beyond this before the next step are ignored
+const MAX_PENDING_TURNS: u8 = 3;
+const FOOD_BLINK_MS: i64 = 160;
+const CRASH_MS: i64 = 1000;
+const CRASH_BLINK_MS: i64 = 100;
+
+#[derive(Clone, Copy)]
+enum Heading {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Heading {
+    fn turn_right(self) -> Self {
+        match self {
+            Heading::Up => Heading::Right,
+            Heading::Right => Heading::Down,
+            Heading::Down => Heading::Left,
+            Heading::Left => Heading::Up,
+        }
+    }
+
+    /// The cell next to `x`, `y` this way, wrapping around the edges
+    fn step(self, (x, y): (u8, u8)) -> (u8, u8) {
+        match self {
+            Heading::Up => (x, (y + 7) % 8),
+            Heading::Right => ((x + 1) % 8, y),
+            Heading::Down => (x, (y + 1) % 8),
+            Heading::Left => ((x + 7) % 8, y),
+        }
+    }
+}
+
+/// Eat the food and grow, every press turns the snake right
+#[derive(Clone)]
+pub struct Snake {
+    rng: Rng,
+    /// Head first
+    body: Deque<(u8, u8), { MAX_LENGTH + 1 }>,
+    heading: Heading,
+    pending_turns: u8,
+    food: (u8, u8),
+    next_step_ms: i64,
+    clock_ms: i64,
+    score: u32,
+    crashed_ms: Option<i64>,
+}
+
+impl Snake {
+    pub fn new() -> Self {
+        let mut body = Deque::new();
+        body.push_back((3, 4)).ok();
+        body.push_back((2, 4)).ok();
+        let mut snake = Self {
+            rng: Rng::from_time(),
+            body,
+            heading: Heading::Right,
+            pending_turns: 0,
+            food: (0, 0),
+            next_step_ms: START_STEP_MS,
+            clock_ms: 0,
+            score: 0,
+            crashed_ms: None,
+        };
+        snake.place_food();
+        snake
+    }
+
+    fn place_food(&mut self) {
+        loop {
+            let food = (self.rng.range(0..8) as u8, self.rng.range(0..8) as u8);
+            if !self.body.iter().any(|cell| *cell == food) {
+                self.food = food;
+                return;
+            }
+        }
+    }
+
+    fn step(&mut self) {
+        if self.pending_turns > 0 {
+            self.pending_turns -= 1;
+            self.heading = self.heading.turn_right();
+        }
+
+        let head = self.heading.step(*self.body.front().expect("a head"));
+        let grows = head == self.food && self.body.len() < MAX_LENGTH;
+        if !grows {
+            self.body.pop_back();
+        }
+        if self.body.iter().any(|cell| *cell == head) {
+            self.crashed_ms = Some(0);
+            return;
+        }
+        self.body.push_front(head).ok();
+
+        if head == self.food {
+            self.score += 1;
+            self.place_food();
+        }
+    }
+}
+
+impl Default for Snake {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for Snake {
+    fn press(&mut self, _config: &GameConfig) {
+        self.pending_turns = (self.pending_turns + 1).min(MAX_PENDING_TURNS);
+    }
+
+    fn update(&mut self, delta_time_ms: u64, _config: &GameConfig) {
+        let delta_time_ms = delta_time_ms as i64;
+        self.clock_ms += delta_time_ms;
+        if let Some(crashed_ms) = &mut self.crashed_ms {
+            *crashed_ms += delta_time_ms;
+            return;
+        }
+
+        self.next_step_ms -= delta_time_ms;
+        if self.next_step_ms <= 0 {
+            self.next_step_ms +=
+                (START_STEP_MS - self.score as i64 * STEP_SPEEDUP_MS).max(FASTEST_STEP_MS);
+            self.step();
+        }
+    }
+
+    fn draw(&self, _alpha: Fixed, dot_matrix: &mut DotMatrix<'_>) {
+        let blink_off = self
+            .crashed_ms
+            .is_some_and(|crashed_ms| (crashed_ms / CRASH_BLINK_MS) % 2 == 1);
+        if !blink_off {
+            for (x, y) in self.body.iter() {
+                dot_matrix.put(*x, *y);
+            }
+        }
+        if (self.clock_ms / FOOD_BLINK_MS) % 2 == 0 {
+            dot_matrix.put(self.food.0, self.food.1);
+        }
+    }
+
+    fn score(&self) -> u32 {
+        self.score
+    }
+
+    fn is_over(&self) -> bool {
+        self.crashed_ms
+            .is_some_and(|crashed_ms| crashed_ms >= CRASH_MS)
+    }
+}
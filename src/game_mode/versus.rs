@@ -0,0 +1,313 @@
+use heapless::{format, String};
+use log::info;
+
+use crate::{
+    dot_matrix::DotMatrix, fixed::Fixed, game_config::GameConfig, input::Player, rng::Rng,
+};
+
+use super::Game;
+
+const POINTS_TO_WIN: u8 = 5;
+const PADDLE_LENGTH: u8 = 3;
+/// Pixels per second
+const PADDLE_SPEED: Fixed = Fixed::from_int(7);
+const LAST_PADDLE_X: Fixed = Fixed::from_int(8 - PADDLE_LENGTH as i32);
+/// Pixels per second
+const SERVE_X_SPEED: Fixed = Fixed::from_int(3);
+const SERVE_Y_SPEED: Fixed = Fixed::from_int(4);
+/// How much faster the ball gets on every return
+const SPEEDUP: Fixed = Fixed::from_f32(1.05);
+/// Pixels per second added sideways for a return off the very end of a paddle
+const DEFLECTION: Fixed = Fixed::from_int(3);
+const RIGHT_WALL: Fixed = Fixed::from_int(7);
+/// The ball is returned once it reaches the row next to a paddle
+const TOP_ROW: Fixed = Fixed::from_int(1);
+const BOTTOM_ROW: Fixed = Fixed::from_int(6);
+/// Pause between points, showing the score
+const SERVE_MS: i64 = 1500;
+const GAME_OVER_MS: i64 = 1500;
+const BLINK_MS: i64 = 160;
+
+/// A paddle sliding back and forth along the top or bottom row, every press turns it around
+#[derive(Clone)]
+struct Paddle {
+    /// The left end
+    x: Fixed,
+    speed: Fixed,
+    points: u8,
+}
+
+impl Paddle {
+    fn new() -> Self {
+        Self {
+            x: Fixed::from_f32(2.5),
+            speed: PADDLE_SPEED,
+            points: 0,
+        }
+    }
+
+    fn update(&mut self, delta_time_ms: u64) {
+        self.x += self.speed.per_ms(delta_time_ms as i64);
+        if self.x < Fixed::ZERO || self.x > LAST_PADDLE_X {
+            self.x = self.x.clamp(Fixed::ZERO, LAST_PADDLE_X);
+            self.speed = -self.speed;
+        }
+    }
+
+    /// How far off the middle of the paddle (-1..1) a ball at `x` is, if it is over the paddle
+    fn hit(&self, x: Fixed) -> Option<Fixed> {
+        let left = Fixed::from_int(self.x.to_int());
+        let length = Fixed::from_int(PADDLE_LENGTH as i32);
+        let half = length * Fixed::HALF;
+        let into_paddle = Fixed::from_int(x.to_int()) + Fixed::HALF - left;
+        (into_paddle >= Fixed::ZERO && into_paddle < length).then(|| (into_paddle - half) / half)
+    }
+
+    fn draw(&self, row: u8, dot_matrix: &mut DotMatrix<'_>) {
+        for pixel in 0..PADDLE_LENGTH {
+            dot_matrix.put(self.x.to_int() as u8 + pixel, row);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Phase {
+    /// Waiting to serve towards the player who lost the last point
+    Serving {
+        towards: Player,
+        remaining_ms: i64,
+    },
+    Rally,
+    Won {
+        winner: Player,
+        elapsed_ms: i64,
+    },
+}
+
+/// Two players, one button each, with paddles on the top (player two) and bottom (player one) rows.
+/// Missing the ball gives the other player a point and the next serve comes to whoever missed.
+#[derive(Clone)]
+pub struct Versus {
+    rng: Rng,
+    bottom: Paddle,
+    top: Paddle,
+    x: Fixed,
+    y: Fixed,
+    previous_x: Fixed,
+    previous_y: Fixed,
+    x_speed: Fixed,
+    y_speed: Fixed,
+    phase: Phase,
+}
+
+impl Versus {
+    pub fn new() -> Self {
+        let mut rng = Rng::from_time();
+        let towards = if rng.range(0..2) == 0 {
+            Player::One
+        } else {
+            Player::Two
+        };
+        Self {
+            rng,
+            bottom: Paddle::new(),
+            top: Paddle::new(),
+            x: Fixed::ZERO,
+            y: Fixed::ZERO,
+            previous_x: Fixed::ZERO,
+            previous_y: Fixed::ZERO,
+            x_speed: Fixed::ZERO,
+            y_speed: Fixed::ZERO,
+            phase: Phase::Serving {
+                towards,
+                remaining_ms: SERVE_MS,
+            },
+        }
+    }
+
+    fn paddle(&mut self, player: Player) -> &mut Paddle {
+        match player {
+            Player::One => &mut self.bottom,
+            Player::Two => &mut self.top,
+        }
+    }
+
+    fn serve(&mut self, towards: Player) {
+        self.x = Fixed::from_int(self.rng.range(2..6) as i32);
+        self.y = Fixed::from_f32(3.5);
+        self.previous_x = self.x;
+        self.previous_y = self.y;
+        self.x_speed = if self.rng.range(0..2) == 0 {
+            SERVE_X_SPEED
+        } else {
+            -SERVE_X_SPEED
+        };
+        self.y_speed = match towards {
+            Player::One => SERVE_Y_SPEED,
+            Player::Two => -SERVE_Y_SPEED,
+        };
+        self.phase = Phase::Rally;
+    }
+
+    /// `player` let the ball past
+    fn missed(&mut self, player: Player) {
+        let scorer = match player {
+            Player::One => Player::Two,
+            Player::Two => Player::One,
+        };
+        let points = {
+            let paddle = self.paddle(scorer);
+            paddle.points += 1;
+            paddle.points
+        };
+        info!(
+            "Point to {scorer:?}, {}-{}",
+            self.bottom.points, self.top.points
+        );
+        self.phase = if points == POINTS_TO_WIN {
+            Phase::Won {
+                winner: scorer,
+                elapsed_ms: 0,
+            }
+        } else {
+            Phase::Serving {
+                towards: player,
+                remaining_ms: SERVE_MS,
+            }
+        };
+    }
+
+    fn return_ball(&mut self, offset: Fixed) {
+        self.y_speed = -self.y_speed * SPEEDUP;
+        self.x_speed = self.x_speed * SPEEDUP + DEFLECTION * offset;
+    }
+
+    fn draw_points(points: u8, row: u8, dot_matrix: &mut DotMatrix<'_>) {
+        for x in 0..points {
+            dot_matrix.put(x, row);
+        }
+    }
+}
+
+impl Default for Versus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for Versus {
+    /// Only player one's button, see `press_by`
+    fn press(&mut self, config: &GameConfig) {
+        self.press_by(Player::One, config);
+    }
+
+    fn press_by(&mut self, player: Player, _config: &GameConfig) {
+        let paddle = self.paddle(player);
+        paddle.speed = -paddle.speed;
+    }
+
+    fn update(&mut self, delta_time_ms: u64, _config: &GameConfig) {
+        match &mut self.phase {
+            Phase::Serving {
+                towards,
+                remaining_ms,
+            } => {
+                *remaining_ms -= delta_time_ms as i64;
+                if *remaining_ms <= 0 {
+                    let towards = *towards;
+                    self.serve(towards);
+                }
+                return;
+            }
+            Phase::Won { elapsed_ms, .. } => {
+                *elapsed_ms += delta_time_ms as i64;
+                return;
+            }
+            Phase::Rally => {}
+        }
+
+        self.bottom.update(delta_time_ms);
+        self.top.update(delta_time_ms);
+
+        self.previous_x = self.x;
+        self.previous_y = self.y;
+        self.x += self.x_speed.per_ms(delta_time_ms as i64);
+        self.y += self.y_speed.per_ms(delta_time_ms as i64);
+
+        if (self.x < Fixed::ZERO && self.x_speed < Fixed::ZERO)
+            || (self.x >= RIGHT_WALL && self.x_speed > Fixed::ZERO)
+        {
+            self.x = self.x.clamp(Fixed::ZERO, RIGHT_WALL);
+            self.x_speed = -self.x_speed;
+        }
+
+        if self.y < TOP_ROW && self.y_speed < Fixed::ZERO {
+            match self.top.hit(self.x) {
+                Some(offset) => {
+                    self.y = TOP_ROW;
+                    self.return_ball(offset);
+                }
+                None => self.missed(Player::Two),
+            }
+        } else if self.y >= BOTTOM_ROW && self.y_speed > Fixed::ZERO {
+            match self.bottom.hit(self.x) {
+                Some(offset) => {
+                    self.y = BOTTOM_ROW;
+                    self.return_ball(offset);
+                }
+                None => self.missed(Player::One),
+            }
+        }
+    }
+
+    fn draw(&self, alpha: Fixed, dot_matrix: &mut DotMatrix<'_>) {
+        match self.phase {
+            Phase::Serving { .. } => {
+                // The score, player two's above player one's
+                Self::draw_points(self.top.points, 2, dot_matrix);
+                Self::draw_points(self.bottom.points, 5, dot_matrix);
+            }
+            Phase::Rally => {
+                let x = self.previous_x + (self.x - self.previous_x) * alpha;
+                let y = self.previous_y + (self.y - self.previous_y) * alpha;
+                dot_matrix.put(x.to_int().clamp(0, 7) as u8, y.to_int().clamp(0, 7) as u8);
+            }
+            Phase::Won { winner, elapsed_ms } => {
+                // The winner's paddle blinks
+                if (elapsed_ms / BLINK_MS) % 2 == 1 {
+                    match winner {
+                        Player::One => self.top.draw(0, dot_matrix),
+                        Player::Two => self.bottom.draw(7, dot_matrix),
+                    }
+                    return;
+                }
+            }
+        }
+        self.top.draw(0, dot_matrix);
+        self.bottom.draw(7, dot_matrix);
+    }
+
+    /// The winner's points
+    fn score(&self) -> u32 {
+        self.bottom.points.max(self.top.points) as u32
+    }
+
+    fn is_over(&self) -> bool {
+        matches!(self.phase, Phase::Won { elapsed_ms, .. } if elapsed_ms >= GAME_OVER_MS)
+    }
+
+    fn result(&self) -> Option<String<32>> {
+        let winner = if self.bottom.points > self.top.points {
+            1
+        } else {
+            2
+        };
+        Some(
+            format!(
+                " Player {winner} wins! {}-{}",
+                self.bottom.points, self.top.points
+            )
+            .expect("a short result"),
+        )
+    }
+}
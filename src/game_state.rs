@@ -1,97 +1,490 @@
-use heapless::format;
-use log::info;
+use core::fmt::Write;
+
+use embassy_time::Duration;
+use embedded_storage::nor_flash::NorFlash;
+use heapless::{format, String};
+use log::{info, warn};
 
 use crate::{
-    ball::Ball,
     dot_matrix::{DotMatrix, DotMatrixError},
+    fixed::Fixed,
     font,
-    highscore::HighScore,
-    pad::{Pad, PadPosition},
+    game_config::GameConfig,
+    game_mode::{Autopilot, Game, GameMode, Round, Selection, Session},
+    highscore::{Entry, HighScore},
+    input::{ButtonEvent, InputEvent, Player, LONG_PRESS},
+    scoring::BREAKDOWN_SIZE,
+    stats::{Stats, SUMMARY_SIZE},
+    storage::{
+        settings::{Key, Settings},
+        StorageError,
+    },
     text_ticker::TextTicker,
 };
 type Result<T> = core::result::Result<T, GameStateError>;
 #[derive(Debug)]
 pub enum GameStateError {
     AdvanceFailed(DotMatrixError),
+    /// The high scores weren't saved, they are still kept until the power goes
+    Storage(StorageError),
 }
+#[rustfmt::skip]
+const PAUSE_BITMAP: [u8; 8] = [
+    0b00000000,
+    0b01100110,
+    0b01100110,
+    0b01100110,
+    0b01100110,
+    0b01100110,
+    0b01100110,
+    0b00000000,
+];
+
+/// Long enough for a mode's whole high score table
+const TICKER_SIZE: usize = 192;
+/// The dot matrix intensity unless the brightness has been set, see `Key::Brightness`
+pub const DEFAULT_BRIGHTNESS: u8 = 0x0F;
+pub const MAX_BRIGHTNESS: u8 = 0x0F;
+/// Dimmed is this many times darker
+const DIMMED_RATIO: u8 = 5;
+/// In front of the intro and game over text while the high scores can't be saved
+const SAVE_ERROR: &str = " SAVE ERR";
+
 pub enum GameState {
-    Intro(TextTicker<100>),
-    Countdown(i64),
-    Playing { ball: Ball, pad: Pad, score: u32 },
-    GameOver(TextTicker<100>),
+    /// The menu, the last field counts down to the next demo game
+    Intro(TextTicker<TICKER_SIZE>, Selection, i64),
+    /// The attract mode, the pad game playing itself until someone presses the button
+    Demo {
+        round: Round,
+        autopilot: Autopilot,
+        selection: Selection,
+        remaining_ms: i64,
+    },
+    Countdown(i64, Selection),
+    Playing(Session, Selection),
+    Paused {
+        session: Session,
+        selection: Selection,
+        resume_countdown: Option<i64>,
+    },
+    /// A score good enough for the table, waiting for the player's initials one letter at a time
+    EnterInitials {
+        initials: [u8; 3],
+        cursor: usize,
+        score: u32,
+        breakdown: String<BREAKDOWN_SIZE>,
+        selection: Selection,
+    },
+    GameOver(TextTicker<TICKER_SIZE>, Selection),
+    /// The lifetime stats, after the last game mode in the menu
+    Stats(TextTicker<SUMMARY_SIZE>, Selection),
 }
 
 impl GameState {
-    pub fn button_click(&mut self) {
+    /// The menu, scrolling the selected mode and its high score table
+    pub fn intro(selection: Selection, highscore: &HighScore, config: &GameConfig) -> Self {
+        let mut text: String<TICKER_SIZE> = String::new();
+        if highscore.error().is_some() {
+            text.push_str(SAVE_ERROR).expect("a string");
+        }
+        match selection.mode {
+            GameMode::Pads => write!(
+                text,
+                " {} {}",
+                selection.mode.name(),
+                selection.difficulty.name()
+            ),
+            mode => write!(text, " {}", mode.name()),
+        }
+        .expect("a string");
+        match selection.highscore_slot() {
+            Some(slot) => {
+                let table = highscore.table(slot);
+                if table[0].is_empty() {
+                    text.push_str(" No highscores yet").expect("a string");
+                }
+                for (rank, entry) in table
+                    .iter()
+                    .take_while(|entry| !entry.is_empty())
+                    .enumerate()
+                {
+                    write!(text, " {}.{} {}", rank + 1, entry.name(), entry.score)
+                        .expect("a string");
+                }
+            }
+            None => text.push_str(" 2 players").expect("a string"),
+        }
+        GameState::Intro(
+            TextTicker::new(text, config.intro_ticker_speed),
+            selection,
+            config.attract_idle_ms,
+        )
+    }
+
+    pub fn input(
+        &mut self,
+        event: InputEvent,
+        highscore: &mut HighScore,
+        settings: &mut Settings<impl NorFlash>,
+        config: &GameConfig,
+    ) -> Result<()> {
+        if let GameState::Demo { selection, .. } = self {
+            // Wait for the click so it doesn't go on to start a game as soon as the intro shows
+            if !matches!(
+                event.button,
+                ButtonEvent::Press | ButtonEvent::Release { .. }
+            ) {
+                info!("Demo stopped");
+                *self = Self::intro(*selection, highscore, config);
+            }
+            return Ok(());
+        }
+        // The second player's button is only for playing, the menus are player one's
+        if event.player == Player::Two {
+            if let (GameState::Playing(session, _), ButtonEvent::Press) = (&mut *self, event.button)
+            {
+                session.press_by(Player::Two, config);
+            }
+            return Ok(());
+        }
+        match event.button {
+            ButtonEvent::Press => self.button_press(config),
+            ButtonEvent::Release { held } => self.button_release(held, config),
+            ButtonEvent::Click => self.button_click(highscore, settings, config),
+            ButtonEvent::DoubleClick => self.button_double_click(highscore, config),
+            ButtonEvent::LongPress => return self.button_long_press(highscore, settings, config),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn button_click(
+        &mut self,
+        highscore: &HighScore,
+        settings: &mut Settings<impl NorFlash>,
+        config: &GameConfig,
+    ) {
+        match self {
+            GameState::Intro(_, selection, _) | GameState::GameOver(_, selection) => {
+                // Remembered for the next boot, only once a game starts so browsing the menu
+                // doesn't write to flash
+                if let Err(e) = settings.set(Key::Selection, selection) {
+                    warn!("Failed to store the selection: {e:?}");
+                }
+                *self = GameState::Countdown(config.countdown_ms, *selection);
+            }
+            GameState::Stats(_, selection) => {
+                *self = Self::intro(Self::next_mode(*selection), highscore, config);
+            }
+            GameState::EnterInitials {
+                initials, cursor, ..
+            } => Self::next_letter(&mut initials[*cursor], 1),
+            _ => {}
+        }
+    }
+
+    /// Moves `letter` on `steps` letters, going round from Z back to A
+    fn next_letter(letter: &mut u8, steps: u8) {
+        *letter = b'A' + (*letter - b'A' + steps) % 26;
+    }
+
+    pub fn button_double_click(&mut self, highscore: &HighScore, config: &GameConfig) {
         match self {
-            GameState::Intro(_) | GameState::GameOver(_) => {
-                *self = GameState::Countdown(3000);
+            GameState::Intro(_, selection, _) if selection.mode == GameMode::Pads => {
+                let difficulty = selection.difficulty.next();
+                info!("Difficulty: {difficulty:?}");
+                let selection = Selection {
+                    difficulty,
+                    ..*selection
+                };
+                *self = Self::intro(selection, highscore, config);
             }
-            GameState::Playing {
-                pad: Pad::Alive { position, .. },
+            GameState::GameOver(_, selection) => {
+                *self = Self::intro(*selection, highscore, config);
+            }
+            // Two clicks too quick to tell apart
+            GameState::EnterInitials {
+                initials, cursor, ..
+            } => Self::next_letter(&mut initials[*cursor], 2),
+            _ => {}
+        }
+    }
+
+    pub fn button_press(&mut self, config: &GameConfig) {
+        match self {
+            // The pad waits for the release, see `button_release`
+            GameState::Playing(session, selection) if selection.mode != GameMode::Pads => {
+                session.press(config)
+            }
+            GameState::Paused {
+                resume_countdown: resume_countdown @ None,
                 ..
             } => {
-                position.next();
+                *resume_countdown = Some(config.resume_countdown_ms);
             }
             _ => {}
         }
     }
 
-    pub async fn advance(
+    /// The pad only turns once the button is let go, so a press held on to pause doesn't move it
+    /// or count as a near miss first. The other games act on the press, a reaction time or a flap
+    /// can't wait for the release.
+    pub fn button_release(&mut self, held: Duration, config: &GameConfig) {
+        match self {
+            GameState::Playing(session, selection)
+                if selection.mode == GameMode::Pads && held < LONG_PRESS =>
+            {
+                session.press(config)
+            }
+            _ => {}
+        }
+    }
+
+    /// Fails if a new high score couldn't be saved, the game over text then says so
+    pub fn button_long_press(
         &mut self,
-        delta_time_ms: u64,
         highscore: &mut HighScore,
-        dot_matrix: &mut DotMatrix<'_>,
+        settings: &mut Settings<impl NorFlash>,
+        config: &GameConfig,
     ) -> Result<()> {
-        dot_matrix.clear();
         match self {
-            GameState::Intro(text) | GameState::GameOver(text) => {
+            // The stats come after the last mode, before going round to the first again
+            GameState::Intro(_, selection, _) if selection.mode.next() == GameMode::ALL[0] => {
+                info!("Stats");
+                let stats = settings.get_or(Key::Stats, Stats::default());
+                *self = GameState::Stats(
+                    TextTicker::new(stats.summary(), config.intro_ticker_speed),
+                    *selection,
+                );
+            }
+            GameState::Intro(_, selection, _) | GameState::Stats(_, selection) => {
+                let selection = Self::next_mode(*selection);
+                info!("Game mode: {:?}", selection.mode);
+                *self = Self::intro(selection, highscore, config);
+            }
+            GameState::EnterInitials {
+                initials,
+                cursor,
+                score,
+                breakdown,
+                selection,
+            } => {
+                *cursor += 1;
+                if *cursor < initials.len() {
+                    return Ok(());
+                }
+                let entry = Entry {
+                    initials: *initials,
+                    score: *score,
+                };
+                let rank = selection
+                    .highscore_slot()
+                    .and_then(|slot| highscore.insert(slot, entry))
+                    .unwrap_or_default();
+                let saved = highscore.save(settings);
+                let warning = if saved.is_err() { SAVE_ERROR } else { "" };
+                let message = if rank == 0 {
+                    "New highscore!"
+                } else {
+                    "Top ten!"
+                };
+                let text = format!(
+                    "{warning} {message} {}.{} {}{breakdown}",
+                    rank + 1,
+                    entry.name(),
+                    entry.score
+                )
+                .expect("A string");
+                info!("Result:{text}");
+                *self = GameState::GameOver(
+                    TextTicker::new(text, config.game_over_ticker_speed),
+                    *selection,
+                );
+                return saved.map_err(GameStateError::Storage);
+            }
+            GameState::Playing(session, selection) if !session.is_over() => {
+                info!("Paused");
+                *self = GameState::Paused {
+                    session: session.clone(),
+                    selection: *selection,
+                    resume_countdown: None,
+                };
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn next_mode(selection: Selection) -> Selection {
+        Selection {
+            mode: selection.mode.next(),
+            ..selection
+        }
+    }
+
+    /// Adds a finished game to the lifetime stats
+    fn record_stats(session: &Session, settings: &mut Settings<impl NorFlash>) {
+        let mut stats = settings.get_or(Key::Stats, Stats::default());
+        stats.add(session);
+        if let Err(e) = settings.set(Key::Stats, &stats) {
+            warn!("Failed to store the stats: {e:?}");
+        }
+    }
+
+    fn draw_countdown(countdown: i64, dot_matrix: &mut DotMatrix<'_>) {
+        // Rounded up, so it ends on 1 rather than 0
+        let countdown_as_secs = (countdown + 999) / 1000;
+        let countdown_as_bitmap = *font::get_font_data(&((b'0' + countdown_as_secs as u8) as char))
+            .expect("a font for a number");
+
+        dot_matrix.draw(&countdown_as_bitmap);
+        dot_matrix.shift(2, 1);
+    }
+
+    pub fn update(
+        &mut self,
+        delta_time_ms: u64,
+        highscore: &HighScore,
+        settings: &mut Settings<impl NorFlash>,
+        config: &GameConfig,
+    ) {
+        match self {
+            GameState::Intro(text, selection, idle_ms) => {
                 text.update(delta_time_ms);
-                text.draw(dot_matrix);
+                *idle_ms -= delta_time_ms as i64;
+                if *idle_ms <= 0 {
+                    info!("Starting demo");
+                    *self = GameState::Demo {
+                        round: Round::new(selection.difficulty, config),
+                        autopilot: Autopilot::default(),
+                        selection: *selection,
+                        remaining_ms: config.attract_demo_ms,
+                    };
+                }
             }
-            GameState::Countdown(countdown) => {
+            GameState::GameOver(text, _) => {
+                text.update(delta_time_ms);
+            }
+            GameState::Stats(text, _) => {
+                text.update(delta_time_ms);
+            }
+            GameState::EnterInitials { .. } => {}
+            GameState::Demo {
+                round,
+                autopilot,
+                selection,
+                remaining_ms,
+            } => {
+                *remaining_ms -= delta_time_ms as i64;
+                if round.is_over() || *remaining_ms <= 0 {
+                    *self = Self::intro(*selection, highscore, config);
+                    return;
+                }
+                autopilot.update(round, delta_time_ms, config);
+                round.update(delta_time_ms, config);
+            }
+            GameState::Countdown(countdown, selection) => {
                 *countdown -= delta_time_ms as i64;
-                let countdown_as_secs = 1 + (*countdown / 1000);
-                let countdown_as_bitmap =
-                    *font::get_font_data(&((b'0' + countdown_as_secs as u8) as char))
-                        .expect("a font for a number");
-
-                dot_matrix.draw(&countdown_as_bitmap);
-                dot_matrix.shift(2, 1);
 
                 if *countdown <= 0 {
-                    *self = Self::Playing {
-                        ball: Ball::new(3, 3),
-                        pad: Pad::new(PadPosition::Bottom(1.0)),
-                        score: 0,
-                    }
+                    *self = Self::Playing(Session::new(*selection, config), *selection)
                 }
             }
-            GameState::Playing { ball, pad, score } => match pad {
-                Pad::Alive { .. } => {
-                    pad.update(delta_time_ms);
-                    ball.update(pad, delta_time_ms, score);
+            GameState::Paused {
+                session,
+                selection,
+                resume_countdown: Some(countdown),
+            } => {
+                *countdown -= delta_time_ms as i64;
 
-                    pad.draw(dot_matrix);
-                    ball.draw(dot_matrix);
+                if *countdown <= 0 {
+                    info!("Resumed");
+                    *self = Self::Playing(session.clone(), *selection);
                 }
-                Pad::Dead => {
-                    let message = if *score > highscore.get() {
-                        highscore.set(*score);
-                        "New highscore!"
-                    } else {
-                        "Score"
-                    };
+            }
+            GameState::Paused { .. } => {}
+            GameState::Playing(session, selection) => {
+                if !session.is_over() {
+                    session.update(delta_time_ms, config);
+                    return;
+                }
+
+                Self::record_stats(session, settings);
+                let score = session.score();
+                let breakdown = session.breakdown().unwrap_or_default();
+                let text = match (session.result(), selection.highscore_slot()) {
+                    (Some(result), _) => format!("{result}").expect("A string"),
+                    (None, Some(slot)) if highscore.qualifies(slot, score) => {
+                        info!("Score {score} qualifies for the highscore table");
+                        *self = GameState::EnterInitials {
+                            initials: *b"AAA",
+                            cursor: 0,
+                            score,
+                            breakdown,
+                            selection: *selection,
+                        };
+                        return;
+                    }
+                    (None, _) => format!(" Score {score}{breakdown}").expect("A string"),
+                };
+
+                info!("Result:{text}");
 
-                    info!("Result: {message} {score}");
+                *self = GameState::GameOver(
+                    TextTicker::new(text, config.game_over_ticker_speed),
+                    *selection,
+                );
+            }
+        }
+    }
 
-                    *self = GameState::GameOver(TextTicker::new(
-                        format!(" {message} {score}").expect("A string"),
-                        0.014,
-                    ));
+    /// Draws the current state, `alpha` is how far (0..1) we are into the next simulation step
+    pub async fn render(
+        &self,
+        alpha: Fixed,
+        brightness: u8,
+        dot_matrix: &mut DotMatrix<'_>,
+    ) -> Result<()> {
+        dot_matrix.clear();
+        match self {
+            GameState::Intro(text, ..) | GameState::GameOver(text, _) => {
+                text.draw(dot_matrix);
+            }
+            GameState::Stats(text, _) => text.draw(dot_matrix),
+            GameState::Demo { round, .. } => round.draw(alpha, dot_matrix),
+            GameState::Countdown(countdown, _)
+            | GameState::Paused {
+                resume_countdown: Some(countdown),
+                ..
+            } => {
+                Self::draw_countdown(*countdown, dot_matrix);
+            }
+            GameState::Paused {
+                resume_countdown: None,
+                ..
+            } => dot_matrix.draw(&PAUSE_BITMAP),
+            GameState::Playing(session, _) => session.draw(alpha, dot_matrix),
+            GameState::EnterInitials {
+                initials, cursor, ..
+            } => {
+                let letter = *font::get_font_data(&(initials[*cursor] as char)).expect("a letter");
+                dot_matrix.draw(&letter);
+                dot_matrix.shift(2, 0);
+                // Which of the three letters this is
+                for letter in 0..=*cursor as u8 {
+                    dot_matrix.put(2 + letter * 2, 7);
                 }
-            },
+            }
         }
+        let intensity = match self {
+            GameState::Playing(session, _) if session.dimmed() => brightness / DIMMED_RATIO,
+            GameState::Demo { round, .. } if round.dimmed() => brightness / DIMMED_RATIO,
+            _ => brightness,
+        };
+        dot_matrix
+            .set_intensity(intensity)
+            .await
+            .map_err(GameStateError::AdvanceFailed)?;
         dot_matrix
             .flush_buffer_to_spi()
             .await
@@ -99,3 +492,95 @@ impl GameState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        difficulty::Difficulty,
+        storage::{partition::Partition, ram_flash::RamFlash},
+    };
+    use embassy_time::Instant;
+
+    type Flash = RamFlash<{ 5 * 4096 }>;
+
+    const PARTITION: Partition = Partition {
+        offset: 0,
+        size: 5 * 4096,
+    };
+
+    /// A pad game that has just started, with nothing in its way yet
+    fn playing(config: &GameConfig) -> GameState {
+        let selection = Selection {
+            mode: GameMode::Pads,
+            difficulty: Difficulty::Normal,
+        };
+        GameState::Playing(Session::new(selection, config), selection)
+    }
+
+    /// What `session` shows a little later, once a turned pad has had time to slide round
+    fn frame(session: &Session, config: &GameConfig) -> [u8; 8] {
+        let mut session = session.clone();
+        session.update(300, config);
+        let mut dot_matrix = DotMatrix::new();
+        session.draw(Fixed::ZERO, &mut dot_matrix);
+        *dot_matrix.buffer()
+    }
+
+    /// Feeds player one's `buttons` to `state` and returns the frame of the game it ends up in
+    fn press(state: &mut GameState, buttons: &[ButtonEvent], config: &GameConfig) -> [u8; 8] {
+        let mut flash = Flash::new();
+        let mut settings = Settings::new(&mut flash, Ok(PARTITION));
+        let mut highscore = HighScore::new(&mut settings);
+        for &button in buttons {
+            let event = InputEvent::new(Instant::from_ticks(0), button);
+            state
+                .input(event, &mut highscore, &mut settings, config)
+                .unwrap();
+        }
+        match state {
+            GameState::Playing(session, _) | GameState::Paused { session, .. } => {
+                frame(session, config)
+            }
+            _ => panic!("not in a game any more"),
+        }
+    }
+
+    #[test]
+    fn tap_turns_pad() {
+        let config = GameConfig::default();
+        let mut state = playing(&config);
+        let GameState::Playing(session, _) = &state else {
+            unreachable!()
+        };
+        let before = frame(session, &config);
+
+        let released = ButtonEvent::Release {
+            held: Duration::from_millis(100),
+        };
+        assert_eq!(press(&mut state, &[ButtonEvent::Press], &config), before);
+        assert_ne!(press(&mut state, &[released], &config), before);
+        assert!(matches!(state, GameState::Playing(..)));
+    }
+
+    #[test]
+    fn long_press_pauses_without_turning_pad() {
+        let config = GameConfig::default();
+        let mut state = playing(&config);
+        let GameState::Playing(session, _) = &state else {
+            unreachable!()
+        };
+        let before = frame(session, &config);
+
+        let released = ButtonEvent::Release { held: LONG_PRESS };
+        let buttons = [ButtonEvent::Press, ButtonEvent::LongPress, released];
+        assert_eq!(press(&mut state, &buttons, &config), before);
+        assert!(matches!(
+            state,
+            GameState::Paused {
+                resume_countdown: None,
+                ..
+            }
+        ));
+    }
+}
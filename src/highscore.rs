@@ -1,62 +1,391 @@
-use embedded_storage::{ReadStorage, Storage};
-use esp_storage::FlashStorage;
-use log::{debug, info};
+use embedded_storage::nor_flash::NorFlash;
+use log::{debug, error, info};
 
-const FLASH_ADDR: u32 = 0x9000;
-const HEADER: &[u8; 5] = b"m3rra";
+use crate::{
+    difficulty::Difficulty,
+    storage::{
+        settings::{Key, Settings, Value},
+        StorageError,
+    },
+};
 
+/// Where the high score was before the settings log, overwritten in place
+const SINGLE_WRITE_ADDR: u32 = 0x9000;
+/// Followed by the one score there was, from before the game modes and difficulties
+const SINGLE_SCORE_HEADER: &[u8; 5] = b"m3rra";
+const SCORE_SIZE: usize = size_of::<u32>();
+const INITIALS_SIZE: usize = 3;
+const ENTRY_SIZE: usize = INITIALS_SIZE + SCORE_SIZE;
+/// The pad game has one per difficulty, the other single player modes have one each
+pub const SLOT_COUNT: usize = Difficulty::COUNT + 3;
+pub const TABLE_SIZE: usize = 10;
+const TABLE_BYTES: usize = ENTRY_SIZE * TABLE_SIZE;
+const EMPTY_TABLES: [Table; SLOT_COUNT] = [[Entry::EMPTY; TABLE_SIZE]; SLOT_COUNT];
+/// The header and score at `SINGLE_WRITE_ADDR`, in whole words as flash reads need
+const SINGLE_WRITE_SIZE: usize = (SINGLE_SCORE_HEADER.len() + SCORE_SIZE).next_multiple_of(4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    /// Upper case letters
+    pub initials: [u8; INITIALS_SIZE],
+    pub score: u32,
+}
+
+impl Entry {
+    const EMPTY: Entry = Entry {
+        initials: *b"...",
+        score: 0,
+    };
+
+    pub fn is_empty(&self) -> bool {
+        self.score == 0
+    }
+
+    /// The initials as text, for the tickers
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.initials).unwrap_or("...")
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let (initials, score) = bytes.split_at(INITIALS_SIZE);
+        match u32::from_be_bytes(score.try_into().expect("a u32")) {
+            // Still erased flash
+            u32::MAX => Self::EMPTY,
+            score => Self {
+                initials: initials.try_into().expect("initials"),
+                score,
+            },
+        }
+    }
+
+    fn to_bytes(self, bytes: &mut [u8]) {
+        bytes[..INITIALS_SIZE].copy_from_slice(&self.initials);
+        bytes[INITIALS_SIZE..].copy_from_slice(&self.score.to_be_bytes());
+    }
+}
+
+/// Best first, padded out with empty entries
+pub type Table = [Entry; TABLE_SIZE];
+
+/// Every table one after the other, each best first
+impl Value for [Table; SLOT_COUNT] {
+    const SIZE: usize = TABLE_BYTES * SLOT_COUNT;
+
+    fn to_bytes(&self, bytes: &mut [u8]) {
+        for (entry, chunk) in self
+            .iter()
+            .flatten()
+            .zip(bytes.chunks_exact_mut(ENTRY_SIZE))
+        {
+            entry.to_bytes(chunk);
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(core::array::from_fn(|slot| {
+            let table = &bytes[slot * TABLE_BYTES..][..TABLE_BYTES];
+            core::array::from_fn(|rank| {
+                Entry::from_bytes(&table[rank * ENTRY_SIZE..][..ENTRY_SIZE])
+            })
+        }))
+    }
+}
+
+/// The top scores of every slot, kept with the rest of the `Settings` and read from them once up
+/// front. When the flash fails the tables are kept in RAM and `error` says why.
 pub struct HighScore {
-    flash_storage: FlashStorage,
-    score: Option<u32>,
+    tables: [Table; SLOT_COUNT],
+    /// The tables started out from what is stored, rather than empty after it failed to load
+    loaded: bool,
+    /// What went wrong the last time the tables were read or written
+    error: Option<StorageError>,
 }
 
 impl HighScore {
-    pub fn get(&mut self) -> u32 {
-        if self.score.is_none() {
-            let buffer = &mut [0_u8; HEADER.len()];
-            self.flash_storage
-                .read(FLASH_ADDR, buffer)
-                .expect("to be able to read header");
-
-            let correct_header = buffer.iter().zip(HEADER.iter()).all(|(a, b)| a == b);
-            if !correct_header {
-                info!("No previous high score, creating a new one");
-                self.flash_storage
-                    .write(FLASH_ADDR, HEADER)
-                    .expect("a header to be written");
-                self.score = Some(0);
-            } else {
-                let high_score = &mut [0_u8; size_of::<u32>()];
-                self.flash_storage
-                    .read(FLASH_ADDR + HEADER.len() as u32, high_score)
-                    .expect("bytes to be highscore");
-                self.score = Some(u32::from_be_bytes(*high_score));
-            }
+    /// The high scores in `settings`, moving over whatever older versions left elsewhere in flash
+    pub fn new<F: NorFlash>(settings: &mut Settings<F>) -> Self {
+        let mut highscore = Self {
+            tables: EMPTY_TABLES,
+            loaded: false,
+            error: settings.error(),
         };
+        match highscore.load(settings) {
+            Ok(tables) => {
+                highscore.tables = tables;
+                highscore.loaded = true;
+            }
+            Err(e) => {
+                error!("Failed to load the high scores, keeping them in RAM: {e:?}");
+                highscore.error = Some(e);
+            }
+        }
+        highscore
+    }
+
+    fn load<F: NorFlash>(
+        &mut self,
+        settings: &mut Settings<F>,
+    ) -> Result<[Table; SLOT_COUNT], StorageError> {
+        if let Some(tables) = settings.get(Key::HighScores)? {
+            return Ok(tables);
+        }
+        let tables = Self::read_single_write(settings.flash())?;
+        if let Err(e) = settings.set(Key::HighScores, &tables) {
+            error!("Failed to move the high scores: {e:?}");
+            self.error = Some(e);
+        }
+        Ok(tables)
+    }
+
+    /// The tables with the score from where it was kept before the settings, rewritten in place on
+    /// every change
+    fn read_single_write(flash: &mut impl NorFlash) -> Result<[Table; SLOT_COUNT], StorageError> {
+        let bytes = &mut [0_u8; SINGLE_WRITE_SIZE];
+        flash
+            .read(SINGLE_WRITE_ADDR, bytes)
+            .map_err(StorageError::flash)?;
+        let (header, score) = bytes.split_at(SINGLE_SCORE_HEADER.len());
+
+        let mut tables = EMPTY_TABLES;
+        if header == SINGLE_SCORE_HEADER {
+            info!("Moving the high score into the settings");
+            // The pad game at the speed that is now Normal, and nobody entered initials back then.
+            // The header was written before the first score, which may still be erased.
+            match u32::from_be_bytes(score[..SCORE_SIZE].try_into().expect("a u32")) {
+                u32::MAX => {}
+                score => tables[Difficulty::Normal.slot()][0].score = score,
+            }
+        } else {
+            info!("No previous high scores, creating new ones");
+        }
+        Ok(tables)
+    }
+
+    /// Why the tables couldn't be loaded or saved last time, they only last until the power goes
+    pub fn error(&self) -> Option<StorageError> {
+        self.error
+    }
+
+    /// The best score in `slot`, see `Selection::highscore_slot`
+    pub fn get(&self, slot: usize) -> u32 {
+        self.tables[slot][0].score
+    }
+
+    pub fn table(&self, slot: usize) -> &Table {
+        &self.tables[slot]
+    }
 
-        self.score.expect("a highscore")
+    /// Whether `score` makes it into the table for `slot`
+    pub fn qualifies(&self, slot: usize, score: u32) -> bool {
+        score > self.tables[slot][TABLE_SIZE - 1].score
     }
 
-    pub fn set(&mut self, score: u32) {
-        if self.get() != score {
-            debug!("Writing new score to flash ({} -> {})", self.get(), score);
-            self.flash_storage
-                .write(FLASH_ADDR + HEADER.len() as u32, &score.to_be_bytes())
-                .expect("a highscore to be written");
-            self.score = Some(score);
+    /// Puts `entry` in its place in the table for `slot`, below any equal scores, returning the
+    /// rank it got (0 is the best) or `None` if it didn't make it in. Only in RAM until `save`.
+    pub fn insert(&mut self, slot: usize, entry: Entry) -> Option<usize> {
+        let rank = Self::insert_into(&mut self.tables[slot], entry)?;
+        debug!(
+            "New score in slot {slot} ({} {} at {rank})",
+            entry.name(),
+            entry.score
+        );
+        Some(rank)
+    }
+
+    fn insert_into(table: &mut Table, entry: Entry) -> Option<usize> {
+        let rank = table.iter().position(|other| entry.score > other.score)?;
+        table[rank..].rotate_right(1);
+        table[rank] = entry;
+        Some(rank)
+    }
+
+    /// Writes the tables to `settings`, keeping them in RAM when that fails
+    pub fn save<F: NorFlash>(&mut self, settings: &mut Settings<F>) -> Result<(), StorageError> {
+        let result = self.write(settings);
+        self.error = result.err();
+        result
+    }
+
+    /// If the stored tables couldn't be read before, they are read now and the scores since added
+    /// to them, so they aren't written over. Nothing is written while they still can't be read.
+    fn write<F: NorFlash>(&mut self, settings: &mut Settings<F>) -> Result<(), StorageError> {
+        if !self.loaded {
+            let mut tables = self.load(settings)?;
+            for (stored, table) in tables.iter_mut().zip(&self.tables) {
+                for entry in table.iter().take_while(|entry| !entry.is_empty()) {
+                    Self::insert_into(stored, *entry);
+                }
+            }
+            self.tables = tables;
+            self.loaded = true;
         }
+        settings.set(Key::HighScores, &self.tables)
+    }
+
+    /// Empties every table, whether or not the stored ones could be read
+    pub fn reset<F: NorFlash>(&mut self, settings: &mut Settings<F>) -> Result<(), StorageError> {
+        self.tables = EMPTY_TABLES;
+        self.loaded = true;
+        self.save(settings)
     }
 }
 
-impl Default for HighScore {
-    fn default() -> Self {
-        let flash_storage = FlashStorage::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{
+        partition::{Partition, PartitionError},
+        ram_flash::RamFlash,
+    };
+    use embedded_storage::nor_flash::NorFlash;
+
+    /// Big enough for the old score at `SINGLE_WRITE_ADDR` and the partition after them
+    type Flash = RamFlash<0x12000>;
+    /// Too small to reach `SINGLE_WRITE_ADDR`, so the old score can never be read
+    type ShortFlash = RamFlash<{ 8 * 4096 }>;
+
+    const PARTITION: Partition = Partition {
+        offset: 0,
+        size: 5 * 4096,
+    };
+    const GAME: Partition = Partition {
+        offset: 0xC000,
+        size: 5 * 4096,
+    };
+
+    /// Boots with the high scores in `flash`
+    fn open(flash: &mut Flash) -> (Settings<&mut Flash>, HighScore) {
+        let mut settings = Settings::new(flash, Ok(GAME));
+        let highscore = HighScore::new(&mut settings);
+        (settings, highscore)
+    }
+
+    /// Writes the header and `score` at `SINGLE_WRITE_ADDR` like the first version did, or just
+    /// the header for `None`
+    fn write_single(flash: &mut Flash, score: Option<u32>) {
+        let record = &mut [0xFF_u8; SINGLE_WRITE_SIZE];
+        let (header, bytes) = record.split_at_mut(SINGLE_SCORE_HEADER.len());
+        header.copy_from_slice(SINGLE_SCORE_HEADER);
+        if let Some(score) = score {
+            bytes[..SCORE_SIZE].copy_from_slice(&score.to_be_bytes());
+        }
+        flash.write(SINGLE_WRITE_ADDR, record).unwrap();
+    }
+
+    fn entry(initials: &[u8; INITIALS_SIZE], score: u32) -> Entry {
+        Entry {
+            initials: *initials,
+            score,
+        }
+    }
+
+    #[test]
+    fn starts_empty() {
+        let flash = &mut Flash::new();
+        let (_, highscore) = open(flash);
+        assert_eq!(highscore.error(), None);
+        for slot in 0..SLOT_COUNT {
+            assert!(highscore.table(slot).iter().all(Entry::is_empty));
+        }
+    }
+
+    #[test]
+    fn moves_single_score() {
+        let flash = &mut Flash::new();
+        write_single(flash, Some(1234));
+
+        let (_, highscore) = open(flash);
+        assert_eq!(highscore.error(), None);
+        let normal = Difficulty::Normal.slot();
+        assert_eq!(highscore.table(normal)[0].score, 1234);
+        assert!(highscore.table(normal)[1].is_empty());
+        for slot in (0..SLOT_COUNT).filter(|slot| *slot != normal) {
+            assert!(highscore.table(slot).iter().all(Entry::is_empty));
+        }
+
+        // Now in the settings, so it is still there once the old spot is gone
+        flash
+            .erase(SINGLE_WRITE_ADDR, SINGLE_WRITE_ADDR + 4096)
+            .unwrap();
+        let (_, highscore) = open(flash);
+        assert_eq!(highscore.table(normal)[0].score, 1234);
+    }
 
-        info!("Flash size = {}", flash_storage.capacity());
+    #[test]
+    fn skips_single_score_never_written() {
+        let flash = &mut Flash::new();
+        write_single(flash, None);
 
-        Self {
-            flash_storage,
-            score: None,
+        let (_, highscore) = open(flash);
+        assert_eq!(highscore.error(), None);
+        for slot in 0..SLOT_COUNT {
+            assert!(highscore.table(slot).iter().all(Entry::is_empty));
         }
     }
+
+    #[test]
+    fn reads_old_scores_without_partition() {
+        let flash = &mut Flash::new();
+        write_single(flash, Some(42));
+
+        let missing = StorageError::Partition(PartitionError::Missing("game"));
+        let mut settings = Settings::new(flash, Err(missing));
+        let highscore = HighScore::new(&mut settings);
+        assert_eq!(highscore.get(Difficulty::Normal.slot()), 42);
+        // Still nowhere to keep them
+        assert_eq!(highscore.error(), Some(missing));
+    }
+
+    #[test]
+    fn saved_scores_survive_reboot() {
+        let flash = &mut Flash::new();
+        let (mut settings, mut highscore) = open(flash);
+        assert_eq!(highscore.insert(2, entry(b"ABC", 10)), Some(0));
+        assert_eq!(highscore.insert(2, entry(b"DEF", 20)), Some(0));
+        assert_eq!(highscore.insert(2, entry(b"GHI", 10)), Some(2));
+        highscore.save(&mut settings).unwrap();
+
+        let (_, highscore) = open(flash);
+        let expected = [entry(b"DEF", 20), entry(b"ABC", 10), entry(b"GHI", 10)];
+        assert_eq!(highscore.table(2)[..3], expected);
+    }
+
+    #[test]
+    fn reset_clears_tables() {
+        let flash = &mut Flash::new();
+        let (mut settings, mut highscore) = open(flash);
+        highscore.insert(0, entry(b"ABC", 10));
+        highscore.save(&mut settings).unwrap();
+        highscore.reset(&mut settings).unwrap();
+        assert_eq!(highscore.get(0), 0);
+
+        let (_, highscore) = open(flash);
+        for slot in 0..SLOT_COUNT {
+            assert!(highscore.table(slot).iter().all(Entry::is_empty));
+        }
+    }
+
+    #[test]
+    fn keeps_unreadable_scores() {
+        let mut settings = Settings::new(ShortFlash::new(), Ok(PARTITION));
+        let mut highscore = HighScore::new(&mut settings);
+        assert!(highscore.error().is_some());
+
+        highscore.insert(0, entry(b"ABC", 10));
+        assert!(highscore.save(&mut settings).is_err());
+        assert!(highscore.error().is_some());
+        assert_eq!(highscore.get(0), 10);
+        let stored: Option<[Table; SLOT_COUNT]> = settings.get(Key::HighScores).unwrap();
+        assert!(stored.is_none());
+    }
+
+    #[test]
+    fn resets_unreadable_scores() {
+        let mut settings = Settings::new(ShortFlash::new(), Ok(PARTITION));
+        let mut highscore = HighScore::new(&mut settings);
+        highscore.reset(&mut settings).unwrap();
+        assert_eq!(highscore.error(), None);
+        let stored: Option<[Table; SLOT_COUNT]> = settings.get(Key::HighScores).unwrap();
+        assert_eq!(stored, Some(EMPTY_TABLES));
+    }
 }
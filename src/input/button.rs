@@ -0,0 +1,121 @@
+use embassy_time::{with_deadline, Duration, Instant, Timer};
+use esp_hal::gpio::Input;
+
+use crate::input::{ButtonEvent, InputEvent, InputSource, Player, LONG_PRESS};
+
+const DEBOUNCE: Duration = Duration::from_millis(50);
+const DOUBLE_CLICK: Duration = Duration::from_millis(250);
+const HOLD_REPEAT: Duration = Duration::from_millis(200);
+
+enum State {
+    Up,
+    Down {
+        since: Instant,
+        next_hold: Instant,
+        long_press: bool,
+        double_click: bool,
+    },
+    // Released after a short press, waiting to see if a second press makes it a double-click
+    Released(Instant),
+}
+
+/// Turns the raw edges of an active-low button into timestamped `ButtonEvent`s
+pub struct GestureRecogniser<'d> {
+    input: Input<'d>,
+    player: Player,
+    state: State,
+    last_edge: Instant,
+    pending: Option<InputEvent>,
+}
+
+impl<'d> GestureRecogniser<'d> {
+    pub fn new(input: Input<'d>, player: Player) -> Self {
+        Self {
+            input,
+            player,
+            state: State::Up,
+            last_edge: Instant::MIN,
+            pending: None,
+        }
+    }
+
+    fn pressed(&mut self, double_click: bool) -> InputEvent {
+        let now = Instant::now();
+        self.last_edge = now;
+        self.state = State::Down {
+            since: now,
+            next_hold: now + LONG_PRESS,
+            long_press: false,
+            double_click,
+        };
+        InputEvent::new(now, ButtonEvent::Press)
+    }
+
+    async fn recognise(&mut self) -> InputEvent {
+        if let Some(event) = self.pending.take() {
+            return event;
+        }
+
+        // Ignore contact bounce right after an edge
+        Timer::at(self.last_edge + DEBOUNCE).await;
+
+        match &mut self.state {
+            State::Up => {
+                self.input.wait_for_low().await;
+                self.pressed(false)
+            }
+            State::Down {
+                since,
+                next_hold,
+                long_press,
+                double_click,
+            } => match with_deadline(*next_hold, self.input.wait_for_high()).await {
+                Ok(()) => {
+                    let now = Instant::now();
+                    let held = now.duration_since(*since);
+                    self.state = if *long_press || *double_click {
+                        State::Up
+                    } else {
+                        State::Released(now)
+                    };
+                    self.last_edge = now;
+                    InputEvent::new(now, ButtonEvent::Release { held })
+                }
+                Err(_) => {
+                    let at = *next_hold;
+                    *next_hold += HOLD_REPEAT;
+                    let button = if *long_press {
+                        ButtonEvent::HoldRepeat
+                    } else {
+                        *long_press = true;
+                        ButtonEvent::LongPress
+                    };
+                    InputEvent::new(at, button)
+                }
+            },
+            State::Released(released) => {
+                match with_deadline(*released + DOUBLE_CLICK, self.input.wait_for_low()).await {
+                    Ok(()) => {
+                        let press = self.pressed(true);
+                        self.pending = Some(InputEvent::new(press.at, ButtonEvent::DoubleClick));
+                        press
+                    }
+                    Err(_) => {
+                        let at = *released + DOUBLE_CLICK;
+                        self.state = State::Up;
+                        InputEvent::new(at, ButtonEvent::Click)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl InputSource for GestureRecogniser<'_> {
+    async fn next(&mut self) -> InputEvent {
+        InputEvent {
+            player: self.player,
+            ..self.recognise().await
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use core::str::FromStr;
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_time::{Duration, Instant};
+use embedded_io_async::Read;
+use heapless::{Deque, String};
+use log::warn;
+
+use crate::{
+    game_config::Key,
+    input::{tap, ButtonEvent, InputEvent, InputSource, Player},
+};
+
+const LINE_LENGTH: usize = 64;
+
+pub const COMMAND_QUEUE_SIZE: usize = 4;
+pub type ConsoleCommandChannel =
+    Channel<CriticalSectionRawMutex, ConsoleCommand, COMMAND_QUEUE_SIZE>;
+
+/// Console lines that are not button input, handled by the game loop
+#[derive(Debug)]
+pub enum ConsoleCommand {
+    /// `set <key> <value>`, override a `GameConfig` value
+    Set { key: Key, value: f32 },
+    /// `config`, log every `GameConfig` value
+    ShowConfig,
+    /// `save`, store the current `GameConfig` in flash
+    SaveConfig,
+    /// `stats`, log the lifetime `Stats`
+    ShowStats,
+}
+
+/// Line based commands over a serial port, so the game can be played and scripted from a PC.
+///
+/// Button input is one of `press`, `release`, `click`, `double`, `long`, `hold`
+/// or `tap` (a whole press, release and click), prefixed with `p2` for the second player's button.
+/// Anything else is a `ConsoleCommand`.
+pub struct Console<'a, R> {
+    rx: R,
+    commands: &'a ConsoleCommandChannel,
+    line: String<LINE_LENGTH>,
+    pending: Deque<InputEvent, 3>,
+}
+
+impl<'a, R: Read> Console<'a, R> {
+    pub fn new(rx: R, commands: &'a ConsoleCommandChannel) -> Self {
+        Self {
+            rx,
+            commands,
+            line: String::new(),
+            pending: Deque::new(),
+        }
+    }
+
+    async fn read_line(&mut self) {
+        self.line.clear();
+        loop {
+            let mut byte = [0_u8; 1];
+            match self.rx.read(&mut byte).await {
+                Ok(1) => match byte[0] {
+                    b'\r' | b'\n' if !self.line.is_empty() => return,
+                    b'\r' | b'\n' => {}
+                    b => {
+                        if self.line.push(b as char).is_err() {
+                            warn!("Console line too long, ignoring it");
+                            self.line.clear();
+                        }
+                    }
+                },
+                Ok(_) => {}
+                Err(e) => warn!("Failed to read from console: {e:?}"),
+            }
+        }
+    }
+
+    fn parse_command(line: &str) -> Option<ConsoleCommand> {
+        let mut words = line.split_whitespace();
+        let command = match words.next()? {
+            "set" => {
+                let key = Key::from_str(words.next()?).ok()?;
+                let value = words.next()?.parse().ok()?;
+                ConsoleCommand::Set { key, value }
+            }
+            "config" => ConsoleCommand::ShowConfig,
+            "save" => ConsoleCommand::SaveConfig,
+            "stats" => ConsoleCommand::ShowStats,
+            _ => return None,
+        };
+        words.next().is_none().then_some(command)
+    }
+
+    fn parse_line(&mut self) {
+        let at = Instant::now();
+        let line = self.line.trim();
+        let (player, line) = match line.strip_prefix("p2 ") {
+            Some(rest) => (Player::Two, rest.trim_start()),
+            None => (Player::One, line),
+        };
+        let button = match line {
+            "tap" => {
+                for event in tap(at) {
+                    self.pending.push_back(InputEvent { player, ..event }).ok();
+                }
+                return;
+            }
+            "press" => ButtonEvent::Press,
+            "release" => ButtonEvent::Release {
+                held: Duration::from_ticks(0),
+            },
+            "click" => ButtonEvent::Click,
+            "double" => ButtonEvent::DoubleClick,
+            "long" => ButtonEvent::LongPress,
+            "hold" => ButtonEvent::HoldRepeat,
+            line => {
+                match Self::parse_command(line) {
+                    Some(command) => {
+                        if self.commands.try_send(command).is_err() {
+                            warn!("Console command queue full, ignoring '{line}'");
+                        }
+                    }
+                    None => warn!("Unknown console command '{line}'"),
+                }
+                return;
+            }
+        };
+        self.pending
+            .push_back(InputEvent { at, button, player })
+            .ok();
+    }
+}
+
+impl<R: Read> InputSource for Console<'_, R> {
+    async fn next(&mut self) -> InputEvent {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return event;
+            }
+            self.read_line().await;
+            self.parse_line();
+        }
+    }
+}
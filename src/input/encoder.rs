@@ -0,0 +1,58 @@
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::gpio::Input;
+use heapless::Deque;
+use log::trace;
+
+use crate::input::{tap, ButtonEvent, InputEvent, InputSource};
+
+const DEBOUNCE: Duration = Duration::from_millis(2);
+
+/// A quadrature rotary encoder with one full A/B cycle per detent (e.g. an EC11).
+///
+/// Every detent is a tap, ending in a click when turned clockwise and in a double click the other
+/// way. While playing both turn the pad, which only rotates one way, and in the menus one way starts
+/// a game and the other changes the pad game's difficulty.
+pub struct RotaryEncoder<'d> {
+    a: Input<'d>,
+    b: Input<'d>,
+    pending: Deque<InputEvent, 3>,
+}
+
+impl<'d> RotaryEncoder<'d> {
+    pub fn new(a: Input<'d>, b: Input<'d>) -> Self {
+        Self {
+            a,
+            b,
+            pending: Deque::new(),
+        }
+    }
+}
+
+impl InputSource for RotaryEncoder<'_> {
+    async fn next(&mut self) -> InputEvent {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return event;
+            }
+
+            self.a.wait_for_falling_edge().await;
+            let at = Instant::now();
+            Timer::after(DEBOUNCE).await;
+            if self.a.is_high() {
+                continue; // Just a glitch
+            }
+
+            let clockwise = self.b.is_high();
+            trace!("Encoder turned {}", if clockwise { "CW" } else { "CCW" });
+            let [press, release, click] = tap(at);
+            let gesture = if clockwise {
+                click
+            } else {
+                InputEvent::new(at, ButtonEvent::DoubleClick)
+            };
+            for event in [press, release, gesture] {
+                self.pending.push_back(event).ok();
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use embassy_time::{Duration, Instant};
+
+/// Held down this long a press becomes a `ButtonEvent::LongPress` rather than a click
+pub const LONG_PRESS: Duration = Duration::from_millis(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// When the gesture was recognised, so it can be applied at the right point in the simulation
+    pub at: Instant,
+    pub button: ButtonEvent,
+    /// Whose button it was, only the versus mode has a second player
+    pub player: Player,
+}
+
+impl InputEvent {
+    /// An event from the first player's button
+    pub fn new(at: Instant, button: ButtonEvent) -> Self {
+        Self {
+            at,
+            button,
+            player: Player::One,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Player {
+    #[default]
+    One,
+    Two,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEvent {
+    /// The button went down. Reported straight away, before any gesture is known.
+    Press,
+    /// The button went up after being held down for `held`.
+    Release {
+        held: Duration,
+    },
+    /// A short press that was not followed by a second one within the double-click window.
+    Click,
+    DoubleClick,
+    /// The button has been held down for `LONG_PRESS`.
+    LongPress,
+    /// Repeated every `HOLD_REPEAT` while the button stays down after a long press.
+    HoldRepeat,
+}
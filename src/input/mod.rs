@@ -0,0 +1,45 @@
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_time::{Duration, Instant};
+use log::{debug, warn};
+
+pub mod button;
+pub mod console;
+pub mod encoder;
+mod event;
+
+pub use event::{ButtonEvent, InputEvent, Player, LONG_PRESS};
+
+pub const INPUT_QUEUE_SIZE: usize = 16;
+pub type InputEventChannel = Channel<CriticalSectionRawMutex, InputEvent, INPUT_QUEUE_SIZE>;
+
+/// Anything that produces `InputEvent`s for the game: a GPIO button, a rotary encoder, the serial console...
+#[allow(async_fn_in_trait)]
+pub trait InputSource {
+    async fn next(&mut self) -> InputEvent;
+}
+
+/// Queue an event for the game loop, dropping it if the game loop has fallen too far behind
+pub fn post(events: &InputEventChannel, event: InputEvent) {
+    debug!("Input: {event:?}");
+    if events.try_send(event).is_err() {
+        warn!("Input queue full, dropping {event:?}");
+    }
+}
+
+pub async fn forward(mut source: impl InputSource, events: &InputEventChannel) -> ! {
+    loop {
+        post(events, source.next().await);
+    }
+}
+
+/// The events a quick press and release of the button would produce
+fn tap(at: Instant) -> [InputEvent; 3] {
+    [
+        ButtonEvent::Press,
+        ButtonEvent::Release {
+            held: Duration::from_ticks(0),
+        },
+        ButtonEvent::Click,
+    ]
+    .map(|button| InputEvent::new(at, button))
+}
@@ -0,0 +1,149 @@
+use crate::dot_matrix::DotMatrix;
+
+/// A level as two bitmaps laid out like the dot matrix, one byte per row with x = 0 in the top bit.
+/// Walls stay put, bricks break when the ball hits them and the level is done when none are left.
+/// Blocks belong in rows and columns 1..=6, the edges are where the pad goes, and (3, 3) has to be
+/// free since that is where the ball starts.
+pub struct Layout {
+    pub walls: [u8; 8],
+    pub bricks: [u8; 8],
+}
+
+#[rustfmt::skip]
+pub const LAYOUTS: [Layout; 4] = [
+    Layout {
+        walls: [0; 8],
+        bricks: [
+            0b00000000,
+            0b01111110,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+        ],
+    },
+    Layout {
+        walls: [
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00011000,
+            0b00000000,
+            0b00000000,
+        ],
+        bricks: [
+            0b00000000,
+            0b01111110,
+            0b00111100,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+        ],
+    },
+    Layout {
+        walls: [
+            0b00000000,
+            0b01000010,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b01000010,
+            0b00000000,
+        ],
+        bricks: [
+            0b00000000,
+            0b00000000,
+            0b00111100,
+            0b00000000,
+            0b00100100,
+            0b00111100,
+            0b00000000,
+            0b00000000,
+        ],
+    },
+    Layout {
+        walls: [
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b00000110,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+        ],
+        bricks: [
+            0b00000000,
+            0b01010100,
+            0b00101010,
+            0b00000000,
+            0b00000000,
+            0b01010100,
+            0b00101010,
+            0b00000000,
+        ],
+    },
+];
+
+/// The blocks left in the arena, starting out as a copy of one of the `LAYOUTS`
+#[derive(Clone)]
+pub struct Level {
+    /// Counts up forever, the layouts start over when they run out
+    pub number: usize,
+    walls: [u8; 8],
+    bricks: [u8; 8],
+}
+
+impl Level {
+    pub fn new(number: usize) -> Self {
+        let layout = &LAYOUTS[number % LAYOUTS.len()];
+        Self {
+            number,
+            walls: layout.walls,
+            bricks: layout.bricks,
+        }
+    }
+
+    fn mask(x: u8) -> u8 {
+        0b10000000 >> x
+    }
+
+    pub fn is_solid(&self, x: u8, y: u8) -> bool {
+        (self.walls[y as usize] | self.bricks[y as usize]) & Self::mask(x) != 0
+    }
+
+    /// Breaks the brick at `x`, `y` if there is one, returns whether it did
+    pub fn hit(&mut self, x: u8, y: u8) -> bool {
+        let row = &mut self.bricks[y as usize];
+        let broken = *row & Self::mask(x) != 0;
+        *row &= !Self::mask(x);
+        broken
+    }
+
+    /// Removes whatever is at `x`, `y`, so a ball already there doesn't end up stuck inside a block
+    pub fn clear(&mut self, x: u8, y: u8) {
+        self.walls[y as usize] &= !Self::mask(x);
+        self.bricks[y as usize] &= !Self::mask(x);
+    }
+
+    pub fn cleared(&self) -> bool {
+        self.bricks.iter().all(|row| *row == 0)
+    }
+
+    pub fn draw(&self, dot_matrix: &mut DotMatrix) {
+        for y in 0..8 {
+            for x in 0..8 {
+                if self.is_solid(x, y) {
+                    dot_matrix.put(x, y);
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,20 @@
 #![no_std]
 pub mod audio;
 pub mod ball;
+pub mod difficulty;
 pub mod dot_matrix;
+pub mod fixed;
 pub mod font;
+pub mod game_config;
+pub mod game_mode;
 pub mod game_state;
 pub mod highscore;
+pub mod input;
+pub mod level;
 pub mod pad;
+pub mod power_up;
+pub mod rng;
+pub mod scoring;
+pub mod stats;
+pub mod storage;
 pub mod text_ticker;
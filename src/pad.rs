@@ -1,27 +1,157 @@
 use log::info;
 
-use crate::dot_matrix::DotMatrix;
+use crate::{dot_matrix::DotMatrix, fixed::Fixed, game_config::GameConfig};
+
+/// Pixels round the edge of the arena
+const PERIMETER: Fixed = Fixed::from_int(28);
+const CENTRE: Fixed = Fixed::from_int(4);
 
 #[derive(Debug, Clone)]
 pub enum PadPosition {
-    Left(f32),
-    Right(f32),
-    Top(f32),
-    Bottom(f32),
+    Left(Fixed),
+    Right(Fixed),
+    Top(Fixed),
+    Bottom(Fixed),
+    /// The alternative control scheme: a short pad travelling round the edge of the arena that
+    /// turns back on every press. `at` is its back end, counted in pixels clockwise from the top
+    /// left corner, and `speed` is in pixels per second, negative when going anticlockwise.
+    Orbit {
+        at: Fixed,
+        length: u8,
+        speed: Fixed,
+    },
+}
+
+/// A side of the arena, where the pad can be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    /// Which way along the side clockwise is, in x or y
+    fn clockwise(self) -> Fixed {
+        match self {
+            Side::Top | Side::Right => Fixed::ONE,
+            Side::Bottom | Side::Left => -Fixed::ONE,
+        }
+    }
 }
 
 impl PadPosition {
+    /// Where a round starts, with the control scheme picked in the config
+    pub fn start(config: &GameConfig) -> Self {
+        if config.orbit_pad {
+            let length = Fixed::from_int(config.orbit_pad_length as i32);
+            PadPosition::Orbit {
+                // Centred on the bottom row
+                at: Fixed::from_f32(17.5) - length * Fixed::HALF,
+                length: config.orbit_pad_length,
+                speed: config.orbit_pad_speed,
+            }
+        } else {
+            PadPosition::Bottom(Fixed::ONE)
+        }
+    }
+
+    /// The side the pad is on, or the one the middle of it is on when orbiting
+    pub fn side(&self) -> Side {
+        match self {
+            PadPosition::Left(_) => Side::Left,
+            PadPosition::Right(_) => Side::Right,
+            PadPosition::Top(_) => Side::Top,
+            PadPosition::Bottom(_) => Side::Bottom,
+            PadPosition::Orbit { at, length, .. } => {
+                let middle = (*at + Fixed::from_int(*length as i32) * Fixed::HALF)
+                    .rem_euclid(PERIMETER)
+                    .to_int();
+                [Side::Top, Side::Right, Side::Bottom, Side::Left][middle as usize / 7]
+            }
+        }
+    }
+
+    /// Whether a ball should keep its distance from `side`, the whole side for the four-sided pad
+    /// and every side for the orbiting one since it can be anywhere
+    pub fn guards(&self, side: Side) -> bool {
+        match self {
+            PadPosition::Orbit { .. } => true,
+            _ => self.side() == side,
+        }
+    }
+
+    /// Whether a ball reaching `side` at `along` (its x for the top and bottom, y for the sides)
+    /// hits the pad, and if so how far off its middle (-1..1) in x or y
+    pub fn hit(&self, side: Side, along: Fixed) -> Option<Fixed> {
+        match self {
+            PadPosition::Orbit { at, length, .. } => {
+                let perimeter_at = Self::perimeter_at(side, along);
+                let length = Fixed::from_int(*length as i32);
+                // Against the pixels that are lit, not where in between them the pad has got to
+                let into_pad = (perimeter_at - Fixed::from_int(at.to_int())).rem_euclid(PERIMETER);
+                (into_pad < length).then(|| {
+                    let half = length * Fixed::HALF;
+                    (into_pad - half) / half * side.clockwise()
+                })
+            }
+            _ => (self.side() == side).then(|| (along - CENTRE) * Fixed::from_f32(0.25)),
+        }
+    }
+
+    /// For the orbiting pad, whether it is travelling towards the spot `along` `side` the shorter way
+    /// round, so turning it back would take it away from there
+    pub fn approaching(&self, side: Side, along: Fixed) -> bool {
+        match self {
+            PadPosition::Orbit { at, length, speed } => {
+                let middle = *at + Fixed::from_int(*length as i32) * Fixed::HALF;
+                let ahead = (Self::perimeter_at(side, along) - middle).rem_euclid(PERIMETER);
+                (ahead < PERIMETER * Fixed::HALF) == (*speed >= Fixed::ZERO)
+            }
+            _ => false,
+        }
+    }
+
+    /// How far clockwise from the top left corner `along` `side` is, in pixels
+    fn perimeter_at(side: Side, along: Fixed) -> Fixed {
+        match side {
+            Side::Top => along,
+            Side::Right => Fixed::from_int(7) + along,
+            Side::Bottom => Fixed::from_int(22) - along,
+            Side::Left => Fixed::from_int(29) - along,
+        }
+    }
+
+    /// The pixel `index` steps clockwise round the edge from the top left corner
+    fn perimeter_pixel(index: i32) -> (u8, u8) {
+        let index = index.rem_euclid(28) as u8;
+        match index {
+            0..7 => (index, 0),
+            7..14 => (7, index - 7),
+            14..21 => (21 - index, 7),
+            _ => (0, 28 - index),
+        }
+    }
+
     pub fn next(&mut self) {
         *self = match self {
-            PadPosition::Left(_) => PadPosition::Top(0.0),
-            PadPosition::Right(_) => PadPosition::Bottom(0.0),
-            PadPosition::Top(_) => PadPosition::Right(0.0),
-            PadPosition::Bottom(_) => PadPosition::Left(0.0),
+            PadPosition::Left(_) => PadPosition::Top(Fixed::ZERO),
+            PadPosition::Right(_) => PadPosition::Bottom(Fixed::ZERO),
+            PadPosition::Top(_) => PadPosition::Right(Fixed::ZERO),
+            PadPosition::Bottom(_) => PadPosition::Left(Fixed::ZERO),
+            PadPosition::Orbit { at, length, speed } => PadPosition::Orbit {
+                at: *at,
+                length: *length,
+                speed: -*speed,
+            },
         }
     }
 
-    fn to_pixels(slide_amount: f32) -> u8 {
-        (7.0 * (slide_amount * slide_amount * slide_amount)) as u8
+    fn to_pixels(slide_amount: Fixed) -> u8 {
+        (slide_amount * slide_amount * slide_amount)
+            .mul_int(7)
+            .to_int() as u8
     }
 
     fn draw(&self, dot_matrix: &mut DotMatrix) {
@@ -55,22 +185,32 @@ impl PadPosition {
                 }
                 dot_matrix.set_row(7, 0b11111111 << pixels);
             }
+            PadPosition::Orbit { at, length, .. } => {
+                for pixel in 0..*length as i32 {
+                    let (x, y) = Self::perimeter_pixel(at.to_int() + pixel);
+                    dot_matrix.put(x, y);
+                }
+            }
         }
     }
 
-    fn update(&mut self, delta_time_ms: u64) {
+    fn update(&mut self, delta_time_ms: u64, slide_speed: Fixed) {
         match self {
             PadPosition::Left(slide_amount)
             | PadPosition::Right(slide_amount)
             | PadPosition::Top(slide_amount)
             | PadPosition::Bottom(slide_amount) => {
-                *slide_amount = f32::min(*slide_amount + delta_time_ms as f32 * 0.009, 1.0);
+                *slide_amount = Fixed::min(
+                    *slide_amount + slide_speed.mul_int(delta_time_ms as i32),
+                    Fixed::ONE,
+                );
+            }
+            PadPosition::Orbit { at, speed, .. } => {
+                *at = (*at + speed.per_ms(delta_time_ms as i64)).rem_euclid(PERIMETER);
             }
         }
     }
 }
-const MAX_HEALTH: u8 = 4;
-
 #[derive(Clone, Debug)]
 pub enum PadState {
     Normal,
@@ -90,15 +230,15 @@ pub enum Pad {
 }
 
 impl Pad {
-    pub const fn new(initial_position: PadPosition) -> Self {
+    pub const fn new(initial_position: PadPosition, max_health: u8) -> Self {
         Self::Alive {
             state: PadState::Normal,
             position: initial_position,
-            health: MAX_HEALTH,
+            health: max_health,
         }
     }
 
-    pub fn take_damage(&mut self) {
+    pub fn take_damage(&mut self, config: &GameConfig) {
         if let Pad::Alive {
             health,
             state: alive_state,
@@ -109,14 +249,21 @@ impl Pad {
             info!("Health: {}", health);
             if *health == 0 {
                 info!("YOU DED!");
-                *alive_state = PadState::Dying(16 * 70)
+                *alive_state = PadState::Dying(config.dying_ms)
             } else {
-                *alive_state = PadState::Hurting(16 * 10);
+                *alive_state = PadState::Hurting(config.hurt_ms);
             }
         }
     }
 
-    pub fn update(&mut self, delta_time_ms: u64) {
+    pub fn heal(&mut self, max_health: u8) {
+        if let Pad::Alive { health, .. } = self {
+            *health = (*health + 1).min(max_health);
+            info!("Health: {}", health);
+        }
+    }
+
+    pub fn update(&mut self, delta_time_ms: u64, slide_speed: Fixed) {
         match self {
             Pad::Alive {
                 state, position, ..
@@ -135,7 +282,7 @@ impl Pad {
                         }
                     }
                     PadState::Normal => {
-                        position.update(delta_time_ms);
+                        position.update(delta_time_ms, slide_speed);
                     }
                 };
             }
@@ -161,3 +308,33 @@ impl Pad {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orbit(speed: i32) -> PadPosition {
+        // The middle of the pad is two pixels into the top row
+        PadPosition::Orbit {
+            at: Fixed::ZERO,
+            length: 4,
+            speed: Fixed::from_int(speed),
+        }
+    }
+
+    #[test]
+    fn orbit_approaches_spot_ahead() {
+        let ahead = (Side::Top, Fixed::from_int(5));
+        let behind = (Side::Left, Fixed::from_int(3));
+        assert!(orbit(8).approaching(ahead.0, ahead.1));
+        assert!(!orbit(8).approaching(behind.0, behind.1));
+        assert!(!orbit(-8).approaching(ahead.0, ahead.1));
+        assert!(orbit(-8).approaching(behind.0, behind.1));
+    }
+
+    #[test]
+    fn side_pad_never_approaches() {
+        let position = PadPosition::Bottom(Fixed::ONE);
+        assert!(!position.approaching(Side::Bottom, Fixed::from_int(4)));
+    }
+}
@@ -0,0 +1,187 @@
+use log::info;
+
+use crate::{
+    audio::sfx::{self, SoundEffect},
+    dot_matrix::DotMatrix,
+    game_config::GameConfig,
+    rng::Rng,
+};
+
+const PICKUP_BLINK_MS: i64 = 160;
+const SPARKLE_MS: i64 = 250;
+const SHIELD_BLINK_MS: i64 = 400;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerUpKind {
+    /// Balls move at half speed for a while
+    SlowMotion,
+    /// The next pad hit does no damage
+    Shield,
+    /// Gives back one health
+    Health,
+    /// Wall hits score double for a while
+    Multiplier,
+}
+
+impl PowerUpKind {
+    const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::SlowMotion,
+        PowerUpKind::Shield,
+        PowerUpKind::Health,
+        PowerUpKind::Multiplier,
+    ];
+
+    fn sound(self) -> SoundEffect {
+        match self {
+            PowerUpKind::SlowMotion => SoundEffect::SlowMotion,
+            PowerUpKind::Shield => SoundEffect::Shield,
+            PowerUpKind::Health => SoundEffect::Health,
+            PowerUpKind::Multiplier => SoundEffect::Multiplier,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Pickup {
+    kind: PowerUpKind,
+    x: u8,
+    y: u8,
+    remaining_ms: i64,
+}
+
+/// Spawns pickups around the arena and keeps track of the effects they grant
+#[derive(Clone)]
+pub struct PowerUps {
+    rng: Rng,
+    pickup: Option<Pickup>,
+    next_spawn_ms: i64,
+    sparkle: Option<(u8, u8, i64)>,
+    slow_motion_ms: i64,
+    slow_motion_carry_ms: u64,
+    multiplier_ms: i64,
+    shield: bool,
+    clock_ms: i64,
+}
+
+impl PowerUps {
+    pub fn new(config: &GameConfig) -> Self {
+        Self {
+            rng: Rng::from_time(),
+            pickup: None,
+            next_spawn_ms: config.power_up_interval_ms,
+            sparkle: None,
+            slow_motion_ms: 0,
+            slow_motion_carry_ms: 0,
+            multiplier_ms: 0,
+            shield: false,
+            clock_ms: 0,
+        }
+    }
+
+    pub fn update(&mut self, delta_time_ms: u64, config: &GameConfig) {
+        let delta_time_ms = delta_time_ms as i64;
+        self.clock_ms += delta_time_ms;
+        self.slow_motion_ms = (self.slow_motion_ms - delta_time_ms).max(0);
+        self.multiplier_ms = (self.multiplier_ms - delta_time_ms).max(0);
+
+        if let Some((_, _, remaining_ms)) = &mut self.sparkle {
+            *remaining_ms -= delta_time_ms;
+            if *remaining_ms <= 0 {
+                self.sparkle = None;
+            }
+        }
+
+        match &mut self.pickup {
+            Some(pickup) => {
+                pickup.remaining_ms -= delta_time_ms;
+                if pickup.remaining_ms <= 0 {
+                    self.pickup = None;
+                }
+            }
+            None => {
+                self.next_spawn_ms -= delta_time_ms;
+                if self.next_spawn_ms <= 0 {
+                    self.next_spawn_ms = config.power_up_interval_ms;
+                    let kind =
+                        PowerUpKind::ALL[self.rng.range(0..PowerUpKind::ALL.len() as u32) as usize];
+                    self.pickup = Some(Pickup {
+                        kind,
+                        // Keep clear of the walls and the pad
+                        x: self.rng.range(2..6) as u8,
+                        y: self.rng.range(2..6) as u8,
+                        remaining_ms: config.power_up_lifetime_ms,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Picks up the power-up if there is one at `(x, y)`, timed effects start straight away
+    pub fn collect(&mut self, x: u8, y: u8, config: &GameConfig) -> Option<PowerUpKind> {
+        let pickup = self
+            .pickup
+            .take_if(|pickup| pickup.x == x && pickup.y == y)?;
+        info!("Power-up: {:?}", pickup.kind);
+        match pickup.kind {
+            PowerUpKind::SlowMotion => self.slow_motion_ms = config.power_up_duration_ms,
+            PowerUpKind::Shield => self.shield = true,
+            PowerUpKind::Health => {}
+            PowerUpKind::Multiplier => self.multiplier_ms = config.power_up_duration_ms,
+        }
+        self.sparkle = Some((x, y, SPARKLE_MS));
+        sfx::trigger(pickup.kind.sound());
+        Some(pickup.kind)
+    }
+
+    pub fn slow_motion(&self) -> bool {
+        self.slow_motion_ms > 0
+    }
+
+    /// How far the balls move in `delta_time_ms`, half as far during slow motion
+    pub fn ball_time(&mut self, delta_time_ms: u64) -> u64 {
+        if !self.slow_motion() {
+            return delta_time_ms;
+        }
+        let total = delta_time_ms + self.slow_motion_carry_ms;
+        self.slow_motion_carry_ms = total % 2;
+        total / 2
+    }
+
+    pub fn multiplier(&self) -> u32 {
+        if self.multiplier_ms > 0 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Uses up the shield, if there is one
+    pub fn use_shield(&mut self) -> bool {
+        let had_shield = self.shield;
+        if had_shield {
+            info!("Shield used");
+            sfx::trigger(SoundEffect::ShieldBroken);
+        }
+        self.shield = false;
+        had_shield
+    }
+
+    /// The pad blinks while the shield is up
+    pub fn hide_pad(&self) -> bool {
+        self.shield && self.clock_ms % SHIELD_BLINK_MS < SHIELD_BLINK_MS / 4
+    }
+
+    pub fn draw(&self, dot_matrix: &mut DotMatrix) {
+        if let Some(pickup) = &self.pickup {
+            if pickup.remaining_ms % (PICKUP_BLINK_MS * 2) < PICKUP_BLINK_MS {
+                dot_matrix.put(pickup.x, pickup.y);
+            }
+        }
+        if let Some((x, y, _)) = self.sparkle {
+            dot_matrix.put(x - 1, y);
+            dot_matrix.put(x + 1, y);
+            dot_matrix.put(x, y - 1);
+            dot_matrix.put(x, y + 1);
+        }
+    }
+}
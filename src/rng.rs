@@ -0,0 +1,28 @@
+use embassy_time::Instant;
+
+/// Small xorshift generator, good enough for spawning things around the arena
+#[derive(Clone)]
+pub struct Rng(u32);
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    /// Seeded from the clock, which varies with how long the player took to start
+    pub fn from_time() -> Self {
+        Self::new(Instant::now().as_ticks() as u32)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    /// A number in `range`, which must not be empty
+    pub fn range(&mut self, range: core::ops::Range<u32>) -> u32 {
+        range.start + self.next_u32() % (range.end - range.start)
+    }
+}
@@ -0,0 +1,120 @@
+use heapless::{format, String};
+use log::info;
+
+use crate::game_config::GameConfig;
+
+/// Long enough for `Scoring::breakdown` with every number as big as a `u32` goes
+pub const BREAKDOWN_SIZE: usize = 47 + 5 * 10;
+
+/// Adds up the points of a pad game. Every wall or brick bounce in a row without the pad taking
+/// damage extends the combo, and the longer the combo the more each bounce is worth. Turning the pad
+/// away from a ball just before it arrives is a near miss and scores a bonus once the ball bounces.
+#[derive(Clone, Default)]
+pub struct Scoring {
+    total: u32,
+    /// Bounces since the pad last took damage
+    combo: u32,
+    best_combo: u32,
+    wall_points: u32,
+    brick_points: u32,
+    /// What the combo multiplier added on top of the plain wall and brick points
+    combo_points: u32,
+    near_miss_points: u32,
+    wall_hits: u32,
+    /// Times the pad got hit
+    damage_taken: u32,
+    /// A near miss waiting for the ball to bounce off a wall
+    dodged: bool,
+}
+
+impl Scoring {
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    pub fn multiplier(&self, config: &GameConfig) -> u32 {
+        (1 + self.combo / config.combo_step as u32).min(config.max_combo_multiplier as u32)
+    }
+
+    /// A bounce worth `points` before the combo multiplier, bricks when `brick` is set
+    pub fn bounce(&mut self, points: u32, brick: bool, config: &GameConfig) {
+        let combo_points = points * (self.multiplier(config) - 1);
+        if brick {
+            self.brick_points += points;
+        } else {
+            self.wall_points += points;
+            self.wall_hits += 1;
+        }
+        self.combo_points += combo_points;
+        self.total += points + combo_points;
+
+        self.combo += 1;
+        self.best_combo = self.best_combo.max(self.combo);
+        if self.combo % config.combo_step as u32 == 0 {
+            info!("Combo {}, x{}", self.combo, self.multiplier(config));
+        }
+
+        if self.dodged {
+            self.dodged = false;
+            self.near_miss_points += config.near_miss_bonus as u32;
+            self.total += config.near_miss_bonus as u32;
+            info!("Near miss!");
+        }
+    }
+
+    /// The pad was turned away from a ball about to hit it
+    pub fn dodge(&mut self) {
+        self.dodged = true;
+    }
+
+    /// The pad took damage, which ends the combo
+    pub fn damage(&mut self) {
+        self.damage_taken += 1;
+        self.combo = 0;
+        self.dodged = false;
+    }
+
+    pub fn wall_hits(&self) -> u32 {
+        self.wall_hits
+    }
+
+    /// The longest rally, the most bounces in a row without the pad getting hit
+    pub fn best_combo(&self) -> u32 {
+        self.best_combo
+    }
+
+    pub fn damage_taken(&self) -> u32 {
+        self.damage_taken
+    }
+
+    /// Where the points came from, for the game over ticker
+    pub fn breakdown(&self) -> String<BREAKDOWN_SIZE> {
+        format!(
+            " Walls {} Bricks {} Combo +{} Near miss +{} Best combo {}",
+            self.wall_points,
+            self.brick_points,
+            self.combo_points,
+            self.near_miss_points,
+            self.best_combo
+        )
+        .expect("a short breakdown")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breakdown_fits_biggest_numbers() {
+        let scoring = Scoring {
+            wall_points: u32::MAX,
+            brick_points: u32::MAX,
+            combo_points: u32::MAX,
+            near_miss_points: u32::MAX,
+            best_combo: u32::MAX,
+            ..Scoring::default()
+        };
+        assert_eq!(scoring.breakdown().len(), BREAKDOWN_SIZE);
+    }
+}
@@ -0,0 +1,110 @@
+use core::fmt::Write;
+
+use heapless::String;
+use log::info;
+
+use crate::{
+    game_mode::{Game, Session},
+    storage::settings::Value,
+};
+
+/// Long enough for every stat on one line
+pub const SUMMARY_SIZE: usize = 128;
+
+/// Totals over every game ever played, kept in the settings under `Key::Stats`. Wall hits, rallies
+/// and damage only happen in the pad game.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub games: u32,
+    /// Not counting pauses
+    pub play_ms: u64,
+    pub total_score: u64,
+    pub wall_hits: u32,
+    /// The most bounces in a row without the pad getting hit
+    pub longest_rally: u32,
+    /// Times the pad got hit
+    pub damage_taken: u32,
+}
+
+impl Stats {
+    /// Adds a finished game
+    pub fn add(&mut self, session: &Session) {
+        self.games += 1;
+        self.play_ms += session.played_ms();
+        self.total_score += session.score() as u64;
+        if let Some(scoring) = session.scoring() {
+            self.wall_hits += scoring.wall_hits();
+            self.longest_rally = self.longest_rally.max(scoring.best_combo());
+            self.damage_taken += scoring.damage_taken();
+        }
+    }
+
+    pub fn average_score(&self) -> u64 {
+        self.total_score.checked_div(self.games as u64).unwrap_or(0)
+    }
+
+    /// Every stat for the stats screen, with the play time as hours:minutes:seconds
+    pub fn summary(&self) -> String<SUMMARY_SIZE> {
+        let seconds = self.play_ms / 1000;
+        let mut text = String::new();
+        write!(
+            text,
+            " Games {} Time {}:{:02}:{:02} Average {} Walls {} Rally {} Damage {}",
+            self.games,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            self.average_score(),
+            self.wall_hits,
+            self.longest_rally,
+            self.damage_taken
+        )
+        .expect("a short summary");
+        text
+    }
+
+    /// Dumps the stats over serial
+    pub fn log(&self) {
+        info!("games = {}", self.games);
+        info!("play_ms = {}", self.play_ms);
+        info!("total_score = {}", self.total_score);
+        info!("average_score = {}", self.average_score());
+        info!("wall_hits = {}", self.wall_hits);
+        info!("longest_rally = {}", self.longest_rally);
+        info!("damage_taken = {}", self.damage_taken);
+    }
+}
+
+impl Value for Stats {
+    const SIZE: usize = 2 * size_of::<u64>() + 4 * size_of::<u32>();
+
+    fn to_bytes(&self, bytes: &mut [u8]) {
+        let (games, bytes) = bytes.split_at_mut(4);
+        let (play_ms, bytes) = bytes.split_at_mut(8);
+        let (total_score, bytes) = bytes.split_at_mut(8);
+        let (wall_hits, bytes) = bytes.split_at_mut(4);
+        let (longest_rally, damage_taken) = bytes.split_at_mut(4);
+        games.copy_from_slice(&self.games.to_le_bytes());
+        play_ms.copy_from_slice(&self.play_ms.to_le_bytes());
+        total_score.copy_from_slice(&self.total_score.to_le_bytes());
+        wall_hits.copy_from_slice(&self.wall_hits.to_le_bytes());
+        longest_rally.copy_from_slice(&self.longest_rally.to_le_bytes());
+        damage_taken.copy_from_slice(&self.damage_taken.to_le_bytes());
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (games, bytes) = bytes.split_at_checked(4)?;
+        let (play_ms, bytes) = bytes.split_at_checked(8)?;
+        let (total_score, bytes) = bytes.split_at_checked(8)?;
+        let (wall_hits, bytes) = bytes.split_at_checked(4)?;
+        let (longest_rally, damage_taken) = bytes.split_at_checked(4)?;
+        Some(Self {
+            games: u32::from_le_bytes(games.try_into().ok()?),
+            play_ms: u64::from_le_bytes(play_ms.try_into().ok()?),
+            total_score: u64::from_le_bytes(total_score.try_into().ok()?),
+            wall_hits: u32::from_le_bytes(wall_hits.try_into().ok()?),
+            longest_rally: u32::from_le_bytes(longest_rally.try_into().ok()?),
+            damage_taken: u32::from_le_bytes(damage_taken.try_into().ok()?),
+        })
+    }
+}
@@ -0,0 +1,330 @@
+use embedded_storage::nor_flash::NorFlash;
+use log::warn;
+
+/// Marks the start of a record, anything else means the rest of the sector is unused or unreadable
+const MAGIC: u16 = 0x4C47;
+/// Magic, length, sequence number and CRC
+const HEADER_SIZE: u32 = 12;
+/// Records are laid out in words, which suits any flash that reads and writes 1, 2 or 4 bytes at once
+const ALIGN: u32 = 4;
+const CHUNK_SIZE: usize = 64;
+
+#[derive(Clone, Copy)]
+struct Record {
+    sequence: u32,
+    /// Where the header starts
+    offset: u32,
+    len: u32,
+}
+
+impl Record {
+    fn end(&self) -> u32 {
+        self.offset + padded(HEADER_SIZE + self.len)
+    }
+}
+
+fn padded(len: u32) -> u32 {
+    len.div_ceil(ALIGN) * ALIGN
+}
+
+/// CRC-32 as used by zip and ethernet, continuing from `crc`
+fn crc32(mut crc: u32, bytes: &[u8]) -> u32 {
+    crc = !crc;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// An append-only log of records spread over a few flash sectors. Only the newest record matters, so
+/// rather than erasing and rewriting the same spot every time, every record goes after the previous
+/// one and a sector is only erased once the log has gone round all the others. Each record carries a
+/// sequence number and a CRC, so one torn by a power cut mid-write is skipped and the one before it
+/// is found again on the next boot.
+pub struct Log<F> {
+    flash: F,
+    /// Where the first sector starts
+    start: u32,
+    sectors: u32,
+    /// The newest record, once the sectors have been scanned for it
+    newest: Option<Option<Record>>,
+}
+
+impl<F: NorFlash> Log<F> {
+    /// A log in the `sectors` erase sectors of `flash` starting at `start`, which has to be
+    /// sector aligned. At least two sectors are needed so the newest record is never erased.
+    pub fn new(flash: F, start: u32, sectors: u32) -> Self {
+        assert!(sectors >= 2, "a log needs at least two sectors");
+        assert!(
+            start % F::ERASE_SIZE as u32 == 0,
+            "a log has to start on a sector"
+        );
+        assert!(
+            ALIGN % F::READ_SIZE as u32 == 0 && ALIGN % F::WRITE_SIZE as u32 == 0,
+            "the flash has to read and write in units that fit a word"
+        );
+        Self {
+            flash,
+            start,
+            sectors,
+            newest: None,
+        }
+    }
+
+    /// The flash the log is on, for reading whatever else is stored there
+    pub fn flash(&mut self) -> &mut F {
+        &mut self.flash
+    }
+
+    /// The largest record that fits, records don't span sectors
+    pub fn max_len(&self) -> usize {
+        F::ERASE_SIZE - HEADER_SIZE as usize
+    }
+
+    fn sector_start(&self, sector: u32) -> u32 {
+        self.start + sector * F::ERASE_SIZE as u32
+    }
+
+    /// Reads `bytes.len()` bytes at the word aligned `offset`, however long `bytes` is
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), F::Error> {
+        let aligned = bytes.len() / ALIGN as usize * ALIGN as usize;
+        let (body, tail) = bytes.split_at_mut(aligned);
+        self.flash.read(offset, body)?;
+        if !tail.is_empty() {
+            let word = &mut [0_u8; ALIGN as usize];
+            self.flash.read(offset + aligned as u32, word)?;
+            tail.copy_from_slice(&word[..tail.len()]);
+        }
+        Ok(())
+    }
+
+    /// The record starting at `offset` if there is a whole and intact one
+    fn read_record(&mut self, offset: u32, sector_end: u32) -> Result<Option<Record>, F::Error> {
+        if offset + HEADER_SIZE > sector_end {
+            return Ok(None);
+        }
+        let header = &mut [0_u8; HEADER_SIZE as usize];
+        self.read(offset, header)?;
+        let magic = u16::from_le_bytes([header[0], header[1]]);
+        let len = u16::from_le_bytes([header[2], header[3]]) as u32;
+        let sequence = u32::from_le_bytes(header[4..8].try_into().expect("a u32"));
+        let crc = u32::from_le_bytes(header[8..12].try_into().expect("a u32"));
+        let record = Record {
+            sequence,
+            offset,
+            len,
+        };
+        if magic != MAGIC || record.end() > sector_end {
+            return Ok(None);
+        }
+
+        let mut actual = crc32(0, &header[..8]);
+        let chunk = &mut [0_u8; CHUNK_SIZE];
+        let mut read = 0;
+        while read < len {
+            let size = (len - read).min(CHUNK_SIZE as u32) as usize;
+            self.read(offset + HEADER_SIZE + read, &mut chunk[..size])?;
+            actual = crc32(actual, &chunk[..size]);
+            read += size as u32;
+        }
+        Ok((actual == crc).then_some(record))
+    }
+
+    /// Walks every sector for the record with the highest sequence number. Each sector is read up to
+    /// the first thing that isn't a valid record, which is either unused space or a torn write.
+    fn find_newest(&mut self) -> Result<Option<Record>, F::Error> {
+        if let Some(newest) = self.newest {
+            return Ok(newest);
+        }
+        let mut newest: Option<Record> = None;
+        for sector in 0..self.sectors {
+            let sector_end = self.sector_start(sector + 1);
+            let mut offset = self.sector_start(sector);
+            while let Some(record) = self.read_record(offset, sector_end)? {
+                if newest.is_none_or(|newest| record.sequence > newest.sequence) {
+                    newest = Some(record);
+                }
+                offset = record.end();
+            }
+        }
+        self.newest = Some(newest);
+        Ok(newest)
+    }
+
+    /// Copies the newest record into `bytes`, returning its length, or `None` if there isn't one yet
+    /// or it is too long for `bytes`, which only a record written by something else could be
+    pub fn read_latest(&mut self, bytes: &mut [u8]) -> Result<Option<usize>, F::Error> {
+        let Some(record) = self.find_newest()? else {
+            return Ok(None);
+        };
+        let len = record.len as usize;
+        if len > bytes.len() {
+            warn!("Ignoring a {len} byte record, at most {} fit", bytes.len());
+            return Ok(None);
+        }
+        self.read(record.offset + HEADER_SIZE, &mut bytes[..len])?;
+        Ok(Some(len))
+    }
+
+    fn is_erased(&mut self, from: u32, to: u32) -> Result<bool, F::Error> {
+        let chunk = &mut [0_u8; CHUNK_SIZE];
+        let mut offset = from;
+        while offset < to {
+            let size = (to - offset).min(CHUNK_SIZE as u32) as usize;
+            self.read(offset, &mut chunk[..size])?;
+            if chunk[..size].iter().any(|byte| *byte != 0xFF) {
+                return Ok(false);
+            }
+            offset += size as u32;
+        }
+        Ok(true)
+    }
+
+    /// Where a record of `len` bytes goes: straight after the newest one if there is still erased
+    /// space for it in the same sector, otherwise at the start of the next sector, which is erased
+    fn next_offset(&mut self, newest: Option<Record>, len: u32) -> Result<u32, F::Error> {
+        let size = padded(HEADER_SIZE + len);
+        let sector = match newest {
+            Some(newest) => {
+                let sector = (newest.offset - self.start) / F::ERASE_SIZE as u32;
+                let offset = newest.end();
+                let sector_end = self.sector_start(sector + 1);
+                if offset + size <= sector_end && self.is_erased(offset, offset + size)? {
+                    return Ok(offset);
+                }
+                (sector + 1) % self.sectors
+            }
+            None => 0,
+        };
+        let offset = self.sector_start(sector);
+        self.flash.erase(offset, offset + F::ERASE_SIZE as u32)?;
+        Ok(offset)
+    }
+
+    /// Adds `bytes` as the newest record
+    pub fn append(&mut self, bytes: &[u8]) -> Result<(), F::Error> {
+        assert!(bytes.len() <= self.max_len(), "the record is too long");
+        let newest = self.find_newest()?;
+        let len = bytes.len() as u32;
+        let sequence = newest.map_or(0, |newest| newest.sequence.wrapping_add(1));
+        let offset = self.next_offset(newest, len)?;
+
+        let mut header = [0_u8; HEADER_SIZE as usize];
+        header[0..2].copy_from_slice(&MAGIC.to_le_bytes());
+        header[2..4].copy_from_slice(&(len as u16).to_le_bytes());
+        header[4..8].copy_from_slice(&sequence.to_le_bytes());
+        let crc = crc32(crc32(0, &header[..8]), bytes);
+        header[8..12].copy_from_slice(&crc.to_le_bytes());
+
+        // The payload first and the header last, so a record only looks like one once it is complete
+        let aligned = bytes.len() / ALIGN as usize * ALIGN as usize;
+        let payload = offset + HEADER_SIZE;
+        self.flash.write(payload, &bytes[..aligned])?;
+        if aligned < bytes.len() {
+            let mut word = [0xFF_u8; ALIGN as usize];
+            word[..bytes.len() - aligned].copy_from_slice(&bytes[aligned..]);
+            self.flash.write(payload + aligned as u32, &word)?;
+        }
+        self.flash.write(offset, &header)?;
+
+        self.newest = Some(Some(Record {
+            sequence,
+            offset,
+            len,
+        }));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ram_flash::{RamFlash, RamFlashError};
+
+    const SECTORS: u32 = 2;
+    type Flash = RamFlash<{ SECTORS as usize * 4096 }>;
+
+    /// The newest record as read after a reboot
+    fn latest(flash: &mut Flash) -> Option<Vec<u8>> {
+        let bytes = &mut [0_u8; 4096];
+        let len = Log::new(flash, 0, SECTORS).read_latest(bytes).unwrap()?;
+        Some(bytes[..len].to_vec())
+    }
+
+    fn append(flash: &mut Flash, bytes: &[u8]) {
+        Log::new(flash, 0, SECTORS).append(bytes).unwrap();
+    }
+
+    /// Cuts the power `after` bytes into appending `bytes`, then checks that after a reboot the
+    /// record before is still the newest and the log takes new records again
+    fn power_cut(flash: &mut Flash, bytes: &[u8], after: usize) {
+        let previous = latest(flash);
+        flash.power_left = Some(after);
+        let result = Log::new(&mut *flash, 0, SECTORS).append(bytes);
+        assert_eq!(
+            result,
+            Err(RamFlashError::PowerLost),
+            "cut after {after} bytes"
+        );
+        flash.power_left = None;
+        assert_eq!(latest(flash), previous, "cut after {after} bytes");
+
+        append(flash, b"after the cut");
+        assert_eq!(latest(flash).as_deref(), Some(&b"after the cut"[..]));
+    }
+
+    #[test]
+    fn reads_back_newest() {
+        let flash = &mut Flash::new();
+        assert_eq!(latest(flash), None);
+        for record in [&b"one"[..], b"two", b"three"] {
+            append(flash, record);
+            assert_eq!(latest(flash).as_deref(), Some(record));
+        }
+    }
+
+    #[test]
+    fn ignores_record_too_long_to_read() {
+        let flash = &mut Flash::new();
+        append(flash, &[0x5A; 100]);
+        let bytes = &mut [0_u8; 10];
+        let len = Log::new(flash, 0, SECTORS).read_latest(bytes);
+        assert_eq!(len, Ok(None));
+    }
+
+    #[test]
+    fn survives_cut_during_payload() {
+        let flash = &mut Flash::new();
+        append(flash, b"before the cut");
+        let payload = [0x5A; 40];
+        for after in 0..payload.len() {
+            power_cut(flash, &payload, after);
+        }
+    }
+
+    #[test]
+    fn survives_cut_during_header() {
+        let flash = &mut Flash::new();
+        append(flash, b"before the cut");
+        let payload = [0x5A; 40];
+        for after in payload.len()..payload.len() + HEADER_SIZE as usize {
+            power_cut(flash, &payload, after);
+        }
+    }
+
+    #[test]
+    fn survives_cut_during_erase() {
+        // Four records fill a sector, so after eight the next one erases the first sector again
+        let record = [0x5A; 1000];
+        for after in [0, 1, 100, 4095] {
+            let flash = &mut Flash::new();
+            for _ in 0..2 * 4 {
+                append(flash, &record);
+            }
+            power_cut(flash, &[0xA5; 1000], after);
+        }
+    }
+}
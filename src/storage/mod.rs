@@ -0,0 +1,24 @@
+use embedded_storage::nor_flash::{NorFlashError, NorFlashErrorKind};
+
+use partition::PartitionError;
+
+pub mod append_log;
+pub mod partition;
+pub mod ram_flash;
+pub mod settings;
+
+/// Why something couldn't be read from or written to flash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageError {
+    /// There is nowhere to store anything
+    Partition(PartitionError),
+    /// Reading, writing or erasing failed
+    Flash(NorFlashErrorKind),
+}
+
+impl StorageError {
+    /// For `map_err`, keeping only the kind so it doesn't depend on which flash it was
+    pub fn flash(error: impl NorFlashError) -> Self {
+        StorageError::Flash(error.kind())
+    }
+}
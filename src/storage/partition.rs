@@ -0,0 +1,61 @@
+use embedded_storage::Storage;
+use esp_bootloader_esp_idf::partitions::{self, PARTITION_TABLE_MAX_LEN};
+use log::info;
+
+/// The data partition everything the game stores goes in, see `partitions.csv`
+pub const GAME_LABEL: &str = "game";
+const DATA_TYPE: u8 = 1;
+const SECTOR_SIZE: u32 = 4096;
+/// The settings log, see `Settings`
+pub const SETTINGS_SECTORS: core::ops::Range<u32> = 0..4;
+/// Where `ConfigStore` keeps the tuning values
+pub const CONFIG_SECTOR: u32 = 4;
+/// Everything above has to fit
+const SECTORS_NEEDED: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionError {
+    /// The partition table couldn't be read or made no sense
+    Table(partitions::Error),
+    /// No data partition with the label, the device was flashed without `partitions.csv`
+    Missing(&'static str),
+    /// Less space than `SECTORS_NEEDED`
+    TooSmall { size: u32 },
+}
+
+/// A region of flash given out by the partition table
+#[derive(Debug, Clone, Copy)]
+pub struct Partition {
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl Partition {
+    /// Looks up the game's data partition in the partition table the bootloader uses
+    pub fn find_game(flash: &mut impl Storage) -> Result<Self, PartitionError> {
+        let buffer = &mut [0_u8; PARTITION_TABLE_MAX_LEN];
+        let table =
+            partitions::read_partition_table(flash, buffer).map_err(PartitionError::Table)?;
+        // The raw type rather than `partition_type`, which panics on subtypes it doesn't know
+        let entry = (0..table.len())
+            .filter_map(|index| table.get_partition(index).ok())
+            .find(|entry| entry.raw_type() == DATA_TYPE && entry.label_as_str() == GAME_LABEL)
+            .ok_or(PartitionError::Missing(GAME_LABEL))?;
+        let partition = Self {
+            offset: entry.offset(),
+            size: entry.len(),
+        };
+        if partition.size < SECTORS_NEEDED * SECTOR_SIZE {
+            return Err(PartitionError::TooSmall {
+                size: partition.size,
+            });
+        }
+        info!("Storage in partition '{GAME_LABEL}' {partition:?}");
+        Ok(partition)
+    }
+
+    /// Where `sector` of the partition starts
+    pub fn sector(&self, sector: u32) -> u32 {
+        self.offset + sector * SECTOR_SIZE
+    }
+}
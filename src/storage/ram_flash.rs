@@ -0,0 +1,113 @@
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RamFlashError {
+    NotAligned,
+    OutOfBounds,
+    /// The simulated power cut happened
+    PowerLost,
+}
+
+impl NorFlashError for RamFlashError {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            RamFlashError::NotAligned => NorFlashErrorKind::NotAligned,
+            RamFlashError::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            RamFlashError::PowerLost => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+/// A NOR flash in RAM with the same sector and word sizes as the ESP32-C3's, for running the storage
+/// code off the device. Like the real thing a write can only clear bits and only an erase sets them
+/// again. Setting `power_left` cuts the power after that many more bytes have been written or
+/// erased, leaving a torn write or a half erased sector behind like an unplugged device would.
+pub struct RamFlash<const SIZE: usize> {
+    bytes: [u8; SIZE],
+    pub power_left: Option<usize>,
+}
+
+impl<const SIZE: usize> RamFlash<SIZE> {
+    /// Erased flash, all ones
+    pub fn new() -> Self {
+        Self {
+            bytes: [0xFF; SIZE],
+            power_left: None,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8; SIZE] {
+        &self.bytes
+    }
+
+    fn check(offset: u32, len: usize, align: usize) -> Result<(), RamFlashError> {
+        if offset as usize % align != 0 || len % align != 0 {
+            Err(RamFlashError::NotAligned)
+        } else if offset as usize + len > SIZE {
+            Err(RamFlashError::OutOfBounds)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Uses up one byte's worth of power
+    fn spend_power(&mut self) -> Result<(), RamFlashError> {
+        match &mut self.power_left {
+            Some(0) => Err(RamFlashError::PowerLost),
+            Some(left) => {
+                *left -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl<const SIZE: usize> Default for RamFlash<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> ErrorType for RamFlash<SIZE> {
+    type Error = RamFlashError;
+}
+
+impl<const SIZE: usize> ReadNorFlash for RamFlash<SIZE> {
+    const READ_SIZE: usize = 4;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        Self::check(offset, bytes.len(), Self::READ_SIZE)?;
+        bytes.copy_from_slice(&self.bytes[offset as usize..][..bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        SIZE
+    }
+}
+
+impl<const SIZE: usize> NorFlash for RamFlash<SIZE> {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = 4096;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        Self::check(from, (to - from) as usize, Self::ERASE_SIZE)?;
+        for byte in from..to {
+            self.spend_power()?;
+            self.bytes[byte as usize] = 0xFF;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        Self::check(offset, bytes.len(), Self::WRITE_SIZE)?;
+        for (at, byte) in (offset as usize..).zip(bytes) {
+            self.spend_power()?;
+            self.bytes[at] &= byte;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use esp_storage::FlashStorage;
+use heapless::Vec;
+use log::{error, info, warn};
+
+use super::{
+    append_log::Log,
+    partition::{Partition, SETTINGS_SECTORS},
+    StorageError,
+};
+
+/// Bump when the way a value is stored changes, and teach `Settings::snapshot` to read the old
+/// layout. Records of any other version are dropped.
+const SCHEMA_VERSION: u8 = 1;
+/// Key and length in front of every value
+const ENTRY_HEADER_SIZE: usize = 3;
+const SNAPSHOT_SIZE: usize = 1024;
+/// The longest record `Settings` writes
+pub const MAX_RECORD_SIZE: usize = SNAPSHOT_SIZE;
+
+/// What is stored, the numbers are what ends up in flash so they must never be reused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Key {
+    HighScores = 1,
+    /// The game mode and difficulty of the last game started
+    Selection = 2,
+    /// Dot matrix intensity, 0..=15
+    Brightness = 3,
+    /// 0 (silent) to `MAX_VOLUME`
+    Volume = 4,
+    /// Totals over every game played, see `Stats`
+    Stats = 5,
+}
+
+/// Something that can be kept in `Settings`, as a fixed number of bytes
+pub trait Value: Sized {
+    const SIZE: usize;
+    fn to_bytes(&self, bytes: &mut [u8]);
+    /// `None` if the bytes don't make a valid value, which is then left out
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+impl Value for u8 {
+    const SIZE: usize = 1;
+
+    fn to_bytes(&self, bytes: &mut [u8]) {
+        bytes[0] = *self;
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bytes.first().copied()
+    }
+}
+
+impl Value for u32 {
+    const SIZE: usize = size_of::<u32>();
+
+    fn to_bytes(&self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_le_bytes());
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+}
+
+/// Typed values by `Key`, all of them written together as one record of an append-only `Log` every
+/// time one changes, so what is read back after a power cut is always a consistent set.
+///
+/// A record is the schema version followed by the values, each as its key, its length as a
+/// little-endian `u16` and its bytes.
+///
+/// Values that fail to be written are kept in RAM and written with the next change, without a log
+/// they are only kept until the power goes.
+///
+/// Works on any `NorFlash`, such as a `RamFlash` off the device.
+pub struct Settings<F = FlashStorage> {
+    store: Store<F>,
+    /// The newest record, read on first use
+    snapshot: Option<Vec<u8, SNAPSHOT_SIZE>>,
+    /// The snapshot has changes the log doesn't
+    unsaved: bool,
+}
+
+/// Where the settings are written
+enum Store<F> {
+    Log(Log<F>),
+    /// Nowhere, the flash is still kept for reading what older versions left on it
+    Unavailable(F, StorageError),
+}
+
+impl<F: NorFlash> Settings<F> {
+    /// The settings log in `partition`. Without a partition the settings are only kept in RAM.
+    pub fn new(flash: F, partition: Result<Partition, StorageError>) -> Self {
+        let store = match partition {
+            Ok(partition) => {
+                let start = partition.sector(SETTINGS_SECTORS.start);
+                Store::Log(Log::new(flash, start, SETTINGS_SECTORS.len() as u32))
+            }
+            Err(e) => {
+                // Flashed without partitions.csv, or the flash is failing
+                error!("No storage, nothing is kept past a power cut: {e:?}");
+                Store::Unavailable(flash, e)
+            }
+        };
+        Self {
+            store,
+            snapshot: None,
+            unsaved: false,
+        }
+    }
+
+    /// Why there is nowhere to keep the settings past a power cut, if so
+    pub fn error(&self) -> Option<StorageError> {
+        match &self.store {
+            Store::Log(_) => None,
+            Store::Unavailable(_, e) => Some(*e),
+        }
+    }
+
+    /// The flash the settings are on, or would be with a partition, for migrating whatever was
+    /// stored elsewhere before
+    pub fn flash(&mut self) -> &mut F {
+        match &mut self.store {
+            Store::Log(log) => log.flash(),
+            Store::Unavailable(flash, _) => flash,
+        }
+    }
+
+    /// Whether every entry fits in the record
+    fn is_valid(snapshot: &[u8]) -> bool {
+        let mut entries = &snapshot[1..];
+        while let [_, low, high, rest @ ..] = entries {
+            let len = u16::from_le_bytes([*low, *high]) as usize;
+            let Some(rest) = rest.get(len..) else {
+                return false;
+            };
+            entries = rest;
+        }
+        entries.is_empty()
+    }
+
+    /// Fails if the newest record can't be read, the settings are then left alone until it can
+    fn snapshot(&mut self) -> Result<&mut Vec<u8, SNAPSHOT_SIZE>, StorageError> {
+        if self.snapshot.is_none() {
+            let record = &mut [0_u8; SNAPSHOT_SIZE];
+            let len = match &mut self.store {
+                Store::Log(log) => log.read_latest(record).map_err(StorageError::flash)?,
+                Store::Unavailable(..) => None,
+            };
+            let snapshot = match len.map(|len| &record[..len]) {
+                None => {
+                    info!("No stored settings");
+                    None
+                }
+                Some(record) if record.first() == Some(&SCHEMA_VERSION) => {
+                    Some(Vec::from_slice(record).expect("a record that fits"))
+                        .filter(|snapshot| Self::is_valid(snapshot))
+                }
+                Some(_) => None,
+            };
+            self.snapshot = Some(snapshot.unwrap_or_else(|| {
+                if len.is_some() {
+                    warn!("Unreadable settings, starting over");
+                }
+                Vec::from_slice(&[SCHEMA_VERSION]).expect("a version")
+            }));
+        }
+        Ok(self.snapshot.as_mut().expect("settings"))
+    }
+
+    /// Where the value of `key` is in the snapshot
+    fn find(snapshot: &[u8], key: Key) -> Option<core::ops::Range<usize>> {
+        let mut at = 1;
+        while at + ENTRY_HEADER_SIZE <= snapshot.len() {
+            let len = u16::from_le_bytes([snapshot[at + 1], snapshot[at + 2]]) as usize;
+            let value = at + ENTRY_HEADER_SIZE..at + ENTRY_HEADER_SIZE + len;
+            if snapshot[at] == key as u8 {
+                return Some(value);
+            }
+            at = value.end;
+        }
+        None
+    }
+
+    pub fn get<T: Value>(&mut self, key: Key) -> Result<Option<T>, StorageError> {
+        let snapshot = self.snapshot()?;
+        let Some(value) = Self::find(snapshot, key) else {
+            return Ok(None);
+        };
+        if value.len() != T::SIZE {
+            warn!(
+                "Stored {key:?} is {} bytes, expected {}",
+                value.len(),
+                T::SIZE
+            );
+            return Ok(None);
+        }
+        Ok(T::from_bytes(&snapshot[value]))
+    }
+
+    /// The value of `key`, or `default` if there is none or the settings can't be read
+    pub fn get_or<T: Value>(&mut self, key: Key, default: T) -> T {
+        match self.get(key) {
+            Ok(value) => value.unwrap_or(default),
+            Err(e) => {
+                warn!("Failed to read {key:?}: {e:?}");
+                default
+            }
+        }
+    }
+
+    /// Stores `value` under `key`, writing all the settings to flash if they changed. When that
+    /// fails the value is still kept in RAM.
+    pub fn set<T: Value>(&mut self, key: Key, value: &T) -> Result<(), StorageError> {
+        let snapshot = self.snapshot()?;
+        // Everything else stays in place, a changed value goes where the old one was
+        let (before, after) = match Self::find(snapshot, key) {
+            Some(old) => (old.start - ENTRY_HEADER_SIZE, old.end),
+            None => (snapshot.len(), snapshot.len()),
+        };
+        let mut updated: Vec<u8, SNAPSHOT_SIZE> =
+            Vec::from_slice(&snapshot[..before]).expect("settings that fit");
+        updated.push(key as u8).expect("room for a key");
+        updated
+            .extend_from_slice(&(T::SIZE as u16).to_le_bytes())
+            .expect("room for a length");
+        let value_at = updated.len();
+        updated
+            .resize(value_at + T::SIZE, 0)
+            .expect("room for the value");
+        value.to_bytes(&mut updated[value_at..]);
+        updated
+            .extend_from_slice(&snapshot[after..])
+            .expect("settings that fit");
+
+        if updated != *snapshot {
+            *snapshot = updated;
+            self.unsaved = true;
+        }
+        if self.unsaved {
+            let log = match &mut self.store {
+                Store::Log(log) => log,
+                Store::Unavailable(_, e) => return Err(*e),
+            };
+            log.append(self.snapshot.as_ref().expect("settings"))
+                .map_err(StorageError::flash)?;
+            self.unsaved = false;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let mut flash_storage = FlashStorage::new();
+
+        info!("Flash size = {}", flash_storage.capacity());
+
+        let partition = Partition::find_game(&mut flash_storage).map_err(StorageError::Partition);
+        Self::new(flash_storage, partition)
+    }
+}